@@ -117,6 +117,18 @@ async fn setup_rpc_module() -> Result<RpcModule<()>, Error> {
             CallError::InvalidParams(anyhow::Error::msg("Relayer parameter is not provided."))
         })?;
 
+        let denomination: u128 = params_iter
+            .next()
+            .ok_or_else(|| {
+                CallError::InvalidParams(anyhow::Error::msg(
+                    "Denomination parameter is not provided.",
+                ))
+            })?
+            .parse::<u128>()
+            .map_err(|_| {
+                CallError::InvalidParams(anyhow::Error::msg("Invalid denomination parameter."))
+            })?;
+
         let inputs = WithdrawInputs {
             nullifier_hash,
             root,
@@ -124,6 +136,7 @@ async fn setup_rpc_module() -> Result<RpcModule<()>, Error> {
             fee,
             recipient,
             relayer,
+            denomination,
         };
 
         let signer: PairSigner<PolkadotConfig, sp_keyring::sr25519::sr25519::Pair> =
@@ -3,6 +3,8 @@ use crate::WithdrawInputs;
 use async_once::AsyncOnce;
 use jsonrpsee::types::error::CallError;
 use lazy_static::lazy_static;
+use plonk_prover::withdrawal_domain;
+use shared::public_types::PoseidonHash;
 use sp_keyring::sr25519::sr25519::Pair;
 use std::ops::Deref;
 use subxt::ext::sp_core::Pair as OtherPair;
@@ -21,6 +23,18 @@ lazy_static! {
             .unwrap()
     });
 }
+/// Address of the Slushie contract which withdrawals are relayed to.
+pub const SLUSHIE_ADDRESS: &str = "5FrfL6HXGbETXUBegkx7vz7LWQ5MNWRGNYE7PEm1w176nLpz";
+
+/// Withdrawal domain of the relayed Slushie contract on the chain with `genesis_hash`.
+/// The contract computes the same domain from its account and the chain identifier.
+pub fn slushie_domain(genesis_hash: H256) -> Result<PoseidonHash, CallError> {
+    let contract = AccountId32::from_string(SLUSHIE_ADDRESS)
+        .map_err(|_| CallError::InvalidParams(anyhow::Error::msg("Invalid contract address.")))?;
+
+    Ok(withdrawal_domain(*contract.as_ref(), genesis_hash.0))
+}
+
 #[subxt::subxt(runtime_metadata_path = "metadata.scale")]
 pub mod node_runtime {}
 
@@ -60,6 +74,7 @@ pub async fn withdraw(
     let mut call_data = Vec::<u8>::new();
     call_data.append(&mut blake2_256("withdraw".as_bytes())[0..4].to_vec());
     call_data.append(&mut scale::Encode::encode(&(
+        inputs.denomination,
         &inputs.nullifier_hash,
         &inputs.root,
         &inputs.proof,
@@ -69,12 +84,15 @@ pub async fn withdraw(
         })?,
     )));
 
+    let contract = AccountId32::from_string(SLUSHIE_ADDRESS)
+        .map_err(|_| CallError::InvalidParams(anyhow::Error::msg("Invalid contract address.")))?;
+    let genesis_hash = API.get().await.deref().genesis_hash();
+
+    // Proof is bound to the contract and the chain, which it is relayed to
+    let domain = slushie_domain(genesis_hash)?;
+
     let tx = node_runtime::tx().contracts().call(
-        MultiAddress::Id(
-            AccountId32::from_string("5FrfL6HXGbETXUBegkx7vz7LWQ5MNWRGNYE7PEm1w176nLpz").map_err(
-                |_| CallError::InvalidParams(anyhow::Error::msg("Invalid contract address.")),
-            )?,
-        ),
+        MultiAddress::Id(contract),
         0,
         49_000_000_000_000,
         None,
@@ -82,9 +100,9 @@ pub async fn withdraw(
     );
     let tx_params = Params::new()
         .tip(PlainTip::new(0))
-        .era(Era::Immortal, API.get().await.deref().genesis_hash());
+        .era(Era::Immortal, genesis_hash);
 
-    if verify_proof(&inputs).await.is_ok() {
+    if verify_proof(&inputs, domain).await.is_ok() {
         let tx_hash = API
             .get()
             .await
@@ -104,7 +122,7 @@ pub async fn withdraw(
 #[cfg(test)]
 
 mod tests {
-    use crate::methods::{flip, node_runtime};
+    use crate::methods::{flip, node_runtime, slushie_domain};
     use crate::public_inputs::WithdrawInputs;
     use crate::utils::verify_proof;
     use crate::withdraw;
@@ -135,9 +153,12 @@ mod tests {
             fee: 1u64,
             recipient: "5Gh8pDNFyir6ZdhkvNy2xGtfUNovRjxCzx5oMhhztXhGX3oZ".to_string(),
             relayer: "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY".to_string(),
+            denomination: 100,
         };
 
-        if verify_proof(&inputs).await.is_ok() {
+        let domain = slushie_domain(api.genesis_hash()).unwrap();
+
+        if verify_proof(&inputs, domain).await.is_ok() {
             withdraw(signer, inputs).await.unwrap();
         }
 
@@ -7,4 +7,5 @@ pub struct WithdrawInputs {
     pub fee: u64,
     pub recipient: String,
     pub relayer: String,
+    pub denomination: u128,
 }
@@ -1,15 +1,25 @@
 use jsonrpsee::types::error::CallError;
-use plonk_prover::verify;
+use lazy_static::lazy_static;
+use plonk_prover::{ProofPublicInputs, SlushieVerifier};
 use shared::constants::DEFAULT_DEPTH;
-use shared::public_types::Pubkey;
+use shared::public_types::{PoseidonHash, Pubkey};
 use sp_core::crypto::{AccountId32, Ss58Codec};
 
 use crate::public_inputs::WithdrawInputs;
 
-/// Proof verification.
-pub async fn verify_proof(inputs: &WithdrawInputs) -> Result<(), Box<dyn std::error::Error>> {
-    let public_parameters = include_bytes!("test-correct-pp");
+lazy_static! {
+    /// Verifier with keys compiled once and shared by all requests.
+    static ref VERIFIER: SlushieVerifier =
+        SlushieVerifier::from_public_parameters::<DEFAULT_DEPTH>(include_bytes!("test-correct-pp"))
+            .expect("Invalid public parameters");
+}
 
+/// Proof verification.
+/// `domain` is the withdrawal domain of the contract and the chain, which the proof is bound to.
+pub async fn verify_proof(
+    inputs: &WithdrawInputs,
+    domain: PoseidonHash,
+) -> Result<(), Box<dyn std::error::Error>> {
     let recipient: Pubkey = AccountId32::from_ss58check(&inputs.recipient)
         .map_err(|_| {
             CallError::InvalidParams(anyhow::Error::msg("Cannot decode recipient parameter."))
@@ -26,45 +36,115 @@ pub async fn verify_proof(inputs: &WithdrawInputs) -> Result<(), Box<dyn std::er
         .try_into()
         .map_err(|_| CallError::InvalidParams(anyhow::Error::msg("Invalid relayer parameter.")))?;
 
-    verify::<{ DEFAULT_DEPTH }>(
-        public_parameters,
-        inputs.nullifier_hash,
-        inputs.root,
-        recipient,
-        relayer,
-        inputs.fee,
-        &inputs.proof,
-    )
-    .map_err(|_| CallError::InvalidParams(anyhow::Error::msg("Invalid proof.")))?;
+    VERIFIER
+        .verify(
+            &ProofPublicInputs {
+                nullifier_hash: inputs.nullifier_hash,
+                root: inputs.root,
+                recipient,
+                relayer,
+                fee: inputs.fee,
+                denomination: inputs.denomination,
+                domain,
+            },
+            &inputs.proof,
+        )
+        .map_err(|_| CallError::InvalidParams(anyhow::Error::msg("Invalid proof.")))?;
 
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::methods::{slushie_domain, SLUSHIE_ADDRESS};
     use crate::{public_inputs::WithdrawInputs, utils::verify_proof};
-    use subxt::ext::sp_core::bytes::from_hex;
+    use dusk_poseidon::sponge;
+    use hex_literal::hex;
+    use plonk_prover::hasher::Poseidon;
+    use plonk_prover::merkle_tree::MerkleTree;
+    use plonk_prover::{generate_commitment, prove, withdrawal_domain};
+    use shared::constants::DEFAULT_DEPTH;
+    use shared::functions::{account_to_scalars, scalar_to_bytes, u128_to_scalar};
+    use shared::public_types::Pubkey;
+    use sp_core::crypto::{AccountId32, Ss58Codec};
+    use subxt::ext::sp_core::{bytes::from_hex, H256};
+
+    /// Genesis hash of the chain which the test proof is bound to
+    const CHAIN_ID: [u8; 32] = [7; 32];
+
+    const RECIPIENT: &str = "5Gh8pDNFyir6ZdhkvNy2xGtfUNovRjxCzx5oMhhztXhGX3oZ";
+    const RELAYER: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
 
     #[tokio::test]
     async fn test_proof_verification_correct() {
-        let proof = hex::encode(include_bytes!("../test_data/test-proof"));
+        let account = |ss58: &str| *AccountId32::from_ss58check(ss58).unwrap().as_ref();
+        let domain = withdrawal_domain(account(SLUSHIE_ADDRESS), CHAIN_ID);
+
+        // Proof is generated for the current circuit with the same public parameters
+        let commitment = generate_commitment();
+        let tree: MerkleTree<DEFAULT_DEPTH, Poseidon> =
+            (&[commitment.commitment_bytes][..]).try_into().unwrap();
+        let proof = prove::<DEFAULT_DEPTH>(
+            include_bytes!("test-correct-pp"),
+            0,
+            tree.root(),
+            tree.opening(0).unwrap(),
+            commitment.nullifier,
+            commitment.randomness,
+            account(RECIPIENT),
+            account(RELAYER),
+            1,
+            100,
+            domain,
+        )
+        .unwrap();
+
         let inputs = WithdrawInputs {
-            nullifier_hash: from_hex(
-                "2478515297534FF5944224B019B82E8242B325B26624825D639C465B360AFFAF",
-            )
-            .unwrap()
-            .try_into()
-            .unwrap(),
-            root: from_hex("5c7ad87d1e4f2a604b7d87e398e664a2fecc28c1d2a24f2226b0a9cd257d519a")
-                .unwrap()
-                .try_into()
-                .unwrap(),
-            proof: from_hex(&proof).unwrap().try_into().unwrap(),
+            nullifier_hash: commitment.nullifier_hash_bytes,
+            root: tree.root(),
+            proof,
             fee: 1u64,
-            recipient: "5Gh8pDNFyir6ZdhkvNy2xGtfUNovRjxCzx5oMhhztXhGX3oZ".to_string(),
-            relayer: "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY".to_string(),
+            recipient: RECIPIENT.to_string(),
+            relayer: RELAYER.to_string(),
+            denomination: 100,
         };
-        assert!(verify_proof(&inputs).await.is_ok());
+        assert!(verify_proof(&inputs, domain).await.is_ok());
+
+        // Proof is not valid for another contract or chain
+        let another_domain = withdrawal_domain(account(SLUSHIE_ADDRESS), [8; 32]);
+        assert!(verify_proof(&inputs, another_domain).await.is_err());
+    }
+
+    /// Relayer encodes accounts and the domain the same way as the contract and the prover:
+    /// accounts are split into their 128-bit halves and the domain hashes the halves
+    /// of the contract account and the genesis hash
+    #[test]
+    fn encodings_match_contract() {
+        // Bytes of the relayer account, which the contract gets as its `AccountId`
+        let relayer: Pubkey = AccountId32::from_ss58check(RELAYER).unwrap().into();
+        assert_eq!(
+            relayer,
+            hex!("d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d")
+        );
+        assert_eq!(
+            account_to_scalars(relayer),
+            [
+                u128_to_scalar(0xd43593c715fdd31c61141abd04a99fd6),
+                u128_to_scalar(0x822c8558854ccde39a5684e7a56da27d),
+            ]
+        );
+
+        // SLUSHIE_ADDRESS is a7c17b50221b16131af2d9d54456e7f2ee5678a71de2850b61b6a080e0ec1c28
+        let chain_id_half = u128_to_scalar(u128::from_be_bytes([7; 16]));
+        assert_eq!(
+            slushie_domain(H256(CHAIN_ID)).unwrap(),
+            scalar_to_bytes(sponge::hash(&[
+                u128_to_scalar(0xa7c17b50221b16131af2d9d54456e7f2),
+                u128_to_scalar(0xee5678a71de2850b61b6a080e0ec1c28),
+                chain_id_half,
+                chain_id_half,
+            ]))
+        );
     }
 
     #[tokio::test]
@@ -79,9 +159,10 @@ mod tests {
                 .unwrap(),
             proof: from_hex(&proof).unwrap().try_into().unwrap(),
             fee: 1u64,
-            recipient: "5Gh8pDNFyir6ZdhkvNy2xGtfUNovRjxCzx5oMhhztXhGX3oZ".to_string(),
-            relayer: "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY".to_string(),
+            recipient: RECIPIENT.to_string(),
+            relayer: RELAYER.to_string(),
+            denomination: 100,
         };
-        // verify_proof(&inputs).await.unwrap();
+        // verify_proof(&inputs, [0; 32]).await.unwrap();
     }
 }
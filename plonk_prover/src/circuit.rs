@@ -3,17 +3,38 @@ use crate::utils::Array;
 use alloc::vec::Vec;
 use dusk_plonk::prelude::*;
 use dusk_poseidon::sponge;
-use shared::functions::bytes_to_u64;
+use shared::functions::bytes_to_scalar;
 use shared::public_types::*;
 
 pub(crate) const CIRCUIT_SIZE: usize = 1 << 15;
 
+///Number of bits in the fee
+///Fee is `u64` in the witness and the public inputs, so it is capped at `2^64 - 1`
+///even for the denominations which don't fit into 64 bits
+const FEE_BITS: usize = 64;
+
+///Number of bits in the denomination and in the recipient payout
+const DENOMINATION_BITS: usize = 128;
+
 ///Constant which should be equal during generating proof and verifying its
 pub(crate) const TRANSCRIPT_INIT: &[u8; 7] = b"slushie";
 
+///Depth of the circuit from the gate indexes of its public inputs:
+///exactly one path bit gate per level is placed between the root and the nullifier hash
+pub(crate) fn circuit_depth(pi_indexes: &[usize]) -> Option<usize> {
+    match pi_indexes {
+        [root, nullifier_hash, ..] => nullifier_hash.checked_sub(root + 1),
+        _ => None,
+    }
+}
+
 /// Circuit that checks:
 /// 1) poseidonHash(k) = h where h is a Public Input
-/// 2) root of tree opening and commitment = R where R is a Public Input
+/// 2) root of tree opening and commitment = R where R is a Public Input,
+///    path bits are boolean
+/// 3) f ≤ N where f and N are Public Inputs
+///
+/// Recipient, relayer, fee, denomination and domain are Public Inputs bound to the proof
 #[allow(non_snake_case)]
 #[derive(Debug, Default)]
 pub(crate) struct SlushieCircuit<const DEPTH: usize> {
@@ -32,12 +53,16 @@ pub(crate) struct SlushieCircuit<const DEPTH: usize> {
     pub R: BlsScalar,
     ///Nullifier hash
     pub h: BlsScalar,
-    ///Recipient address
-    pub A: BlsScalar,
-    ///Relayer address
-    pub t: BlsScalar,
+    ///Recipient address, see `account_to_scalars`
+    pub A: [BlsScalar; 2],
+    ///Relayer address, see `account_to_scalars`
+    pub t: [BlsScalar; 2],
     ///Fee
     pub f: BlsScalar,
+    ///Denomination of the pool
+    pub N: BlsScalar,
+    ///Domain of the contract and the chain
+    pub D: BlsScalar,
 }
 
 impl<const DEPTH: usize> Circuit for SlushieCircuit<DEPTH> {
@@ -46,21 +71,32 @@ impl<const DEPTH: usize> Circuit for SlushieCircuit<DEPTH> {
         //Add secret elements to composer
         let k = composer.append_witness(self.k);
         let r = composer.append_witness(self.r);
+
+        //Root is the first public input and the path bit gates follow it,
+        //so the verifier data tells the depth, see `circuit_depth`
+        let root = composer.append_public_witness(self.R);
+
         let mut path = [composer.append_witness(BlsScalar::zero()); DEPTH];
         for (i, path) in path.iter_mut().enumerate().take(DEPTH) {
             *path = composer.append_witness(self.p.0[i] as u64);
+
+            //Path bit should be either 0 or 1, so the select gates pick exactly one of the children
+            //Leaf index is private and defined by the path bits, so it has no witness of its own
+            composer.component_boolean(*path);
         }
 
         //Add public elements to composer
-        let root = composer.append_public_witness(self.R);
         let nullifier_hash = composer.append_public_witness(self.h);
-        composer.append_public_witness(self.A);
-        composer.append_public_witness(self.t);
-        composer.append_public_witness(self.f);
+        for limb in self.A.iter().chain(self.t.iter()) {
+            composer.append_public_witness(*limb);
+        }
+        let fee = composer.append_public_witness(self.f);
+        let denomination = composer.append_public_witness(self.N);
+        composer.append_public_witness(self.D);
 
         //Compute poseidon hash of nullifier
         let computed_nullifier_hash = sponge::gadget(composer, &[k]);
-       
+
         //Compute poseidon hash of (nullifier || randomness)
         let computed_commitment = sponge::gadget(composer, &[k, r]);
 
@@ -72,7 +108,7 @@ impl<const DEPTH: usize> Circuit for SlushieCircuit<DEPTH> {
             } else {
                 hashes[i - 1]
             };
-            let sister_hash = composer.append_witness(BlsScalar(bytes_to_u64(self.o.0[i])));
+            let sister_hash = composer.append_witness(bytes_to_scalar(self.o.0[i]));
 
             let left = composer.component_select(path[i], sister_hash, current_hash);
             let right = composer.component_select(path[i], current_hash, sister_hash);
@@ -84,6 +120,19 @@ impl<const DEPTH: usize> Circuit for SlushieCircuit<DEPTH> {
         composer.assert_equal(root, hashes[DEPTH - 1]);
         composer.assert_equal(nullifier_hash, computed_nullifier_hash);
 
+        //Check that fee doesn't exceed denomination:
+        //N - f wraps around the field modulus and doesn't fit into 128 bits when f > N
+        composer.component_range(fee, FEE_BITS);
+        composer.component_range(denomination, DENOMINATION_BITS);
+        let payout = composer.gate_add(
+            Constraint::new()
+                .left(1)
+                .a(denomination)
+                .right(-BlsScalar::one())
+                .b(fee),
+        );
+        composer.component_range(payout, DENOMINATION_BITS);
+
         Ok(())
     }
 
@@ -91,13 +140,17 @@ impl<const DEPTH: usize> Circuit for SlushieCircuit<DEPTH> {
         vec![
             self.R.into(),
             self.h.into(),
-            self.A.into(),
-            self.t.into(),
+            self.A[0].into(),
+            self.A[1].into(),
+            self.t[0].into(),
+            self.t[1].into(),
             self.f.into(),
+            self.N.into(),
+            self.D.into(),
         ]
     }
 
     fn padded_gates(&self) -> usize {
         CIRCUIT_SIZE
     }
-}
\ No newline at end of file
+}
@@ -1,6 +1,6 @@
 #![cfg(feature = "proof_generator")]
 
-use rand::RngCore;
+use dusk_bls12_381::BlsScalar;
 use rand_core::OsRng;
 use shared::functions::scalar_to_bytes;
 use shared::public_types::*;
@@ -10,34 +10,33 @@ pub fn generate_commitment() -> GeneratedCommitment {
     // Use OsRng as CSPRNG
     let mut os_rng = OsRng::default();
 
-    // Generate nullifier and randomness
-    let nullifier = os_rng.next_u32();
-    let randomness = os_rng.next_u32();
+    // Generate nullifier and randomness as uniformly random field elements
+    let nullifier = BlsScalar::random(&mut os_rng);
+    let randomness = BlsScalar::random(&mut os_rng);
 
     // Compute commitment
-    let commitment =
-        dusk_poseidon::sponge::hash(&[(nullifier as u64).into(), (randomness as u64).into()]);
+    let commitment = dusk_poseidon::sponge::hash(&[nullifier, randomness]);
 
     // Convert commitment to bytes
     let commitment_bytes = scalar_to_bytes(commitment);
 
     // Compute nullifier hash
-    let nullifier_hash = dusk_poseidon::sponge::hash(&[(nullifier as u64).into()]);
+    let nullifier_hash = dusk_poseidon::sponge::hash(&[nullifier]);
 
     // Convert nullifier hash to bytes
     let nullifier_hash_bytes = scalar_to_bytes(nullifier_hash);
 
     GeneratedCommitment {
-        nullifier,
-        randomness,
+        nullifier: scalar_to_bytes(nullifier),
+        randomness: scalar_to_bytes(randomness),
         commitment_bytes,
         nullifier_hash_bytes,
     }
 }
 
 pub struct GeneratedCommitment {
-    pub nullifier: u32,
-    pub randomness: u32,
+    pub nullifier: Nullifier,
+    pub randomness: Randomness,
     pub commitment_bytes: PoseidonHash,
     pub nullifier_hash_bytes: PoseidonHash,
 }
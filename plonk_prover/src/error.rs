@@ -0,0 +1,74 @@
+use core::fmt;
+
+use dusk_plonk::prelude::Error;
+
+///Errors which may be returned while generating or verifying a proof
+#[derive(Debug)]
+pub enum SlushieProverError {
+    ///Public parameters, prover data, verifier data or one of the keys can't be deserialized
+    KeyDeserialization,
+    ///Leaf index doesn't fit into the tree
+    InvalidIndex,
+    ///Tree opening is not encoded canonically
+    InvalidOpening,
+    ///Leaf index doesn't match the tree opening: a left sister is empty
+    ///or a right sister is filled after an empty one
+    PathMismatch,
+    ///Root computed from the commitment, the opening and the leaf index differs from the root
+    RootMismatch,
+    ///Fee is larger than the denomination
+    FeeExceedsDenomination,
+    ///Nullifier or randomness is not a canonical field element
+    InvalidSecret,
+    ///Public inputs are malformed or not encoded canonically
+    InvalidPublicInputs,
+    ///Proof system failed to compile the circuit, generate or verify the proof
+    ProofSystem(Error),
+}
+
+impl From<Error> for SlushieProverError {
+    fn from(err: Error) -> Self {
+        SlushieProverError::ProofSystem(err)
+    }
+}
+
+impl fmt::Display for SlushieProverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlushieProverError::KeyDeserialization => {
+                write!(f, "Public parameters or keys can't be deserialized")
+            }
+            SlushieProverError::InvalidIndex => write!(f, "Leaf index doesn't fit into the tree"),
+            SlushieProverError::InvalidOpening => {
+                write!(f, "Tree opening is not encoded canonically")
+            }
+            SlushieProverError::PathMismatch => {
+                write!(f, "Leaf index doesn't match the tree opening")
+            }
+            SlushieProverError::RootMismatch => write!(
+                f,
+                "Root computed from the secrets, the opening and the leaf index differs from the root"
+            ),
+            SlushieProverError::FeeExceedsDenomination => {
+                write!(f, "Fee is larger than the denomination")
+            }
+            SlushieProverError::InvalidSecret => {
+                write!(f, "Nullifier or randomness is not a field element")
+            }
+            SlushieProverError::InvalidPublicInputs => {
+                write!(f, "Public inputs are not encoded canonically")
+            }
+            SlushieProverError::ProofSystem(err) => write!(f, "Proof system error: {:?}", err),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "proof_generator"))]
+impl std::error::Error for SlushieProverError {}
+
+#[cfg(feature = "js")]
+impl From<SlushieProverError> for js_sys::Error {
+    fn from(err: SlushieProverError) -> Self {
+        js_sys::Error::new(&alloc::string::ToString::to_string(&err))
+    }
+}
@@ -1,5 +1,5 @@
 #[cfg(feature = "ink")]
-use ink_storage::traits::{PackedLayout, SpreadAllocate, SpreadLayout};
+use ink_storage::traits::{PackedAllocate, PackedLayout, SpreadAllocate, SpreadLayout};
 
 #[cfg(feature = "ink")]
 use ink_env::hash::{Blake2x256, CryptoHash, HashOutput};
@@ -163,6 +163,7 @@ pub trait MerkleTreeHasher:
         + scale::Encode
         + scale::Decode
         + PackedLayout
+        + PackedAllocate
         + SpreadAllocate
         + SpreadLayout
         + StorageLayout
@@ -188,6 +189,7 @@ pub trait MerkleTreeHasher:
     type Output: scale::Encode
         + scale::Decode
         + PackedLayout
+        + PackedAllocate
         + SpreadAllocate
         + SpreadLayout
         + Clone
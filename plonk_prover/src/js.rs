@@ -1,7 +1,7 @@
 #![cfg(feature = "js")]
 
 use alloc::format;
-use alloc::string::ToString;
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use core::array::TryFromSliceError;
 
@@ -14,10 +14,9 @@ use shared::public_types::*;
 use crate::commitment_generation::{generate_commitment as commitment_gen, GeneratedCommitment};
 use crate::hasher::Poseidon;
 use crate::merkle_tree::{MerkleTree, MerkleTreeError};
-use crate::proof_generation::prove;
-
-#[cfg(feature = "js_include_pd")]
-use crate::proof_generation::prove_with_vd;
+use crate::note::Note;
+use crate::proof_generation::{SlushieProver, WithdrawalWitness};
+use crate::proof_verification::withdrawal_domain;
 
 const SERIALIZED_PUBLIC_PARAMETERS: &[u8] = include_bytes!("../../public-parameters/pp-test");
 
@@ -27,6 +26,69 @@ const PROVER_DATA: &[u8] = include_bytes!("../../public-parameters/pd-test");
 #[cfg(feature = "js_include_pd")]
 const COMMIT_KEY: &[u8] = include_bytes!("../../public-parameters/commit-key-test");
 
+///Prover which compiles the circuit once and can be reused in frontend for many proofs
+#[wasm_bindgen(js_name = SlushieProver)]
+pub struct JsSlushieProver(SlushieProver<DEFAULT_DEPTH>);
+
+#[wasm_bindgen(js_class = SlushieProver)]
+impl JsSlushieProver {
+    ///Create prover by compiling circuit with included public parameters
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Result<JsSlushieProver, js_sys::Error> {
+        SlushieProver::new(SERIALIZED_PUBLIC_PARAMETERS)
+            .map(JsSlushieProver)
+            .map_err(js_sys::Error::from)
+    }
+
+    ///Create prover from serialized prover data and commitment key
+    #[wasm_bindgen(js_name = fromProverData)]
+    pub fn from_prover_data(pd: &[u8], ck: &[u8]) -> Result<JsSlushieProver, js_sys::Error> {
+        SlushieProver::from_prover_data(pd, ck)
+            .map(JsSlushieProver)
+            .map_err(js_sys::Error::from)
+    }
+
+    ///Generate serialized proof without compiling circuit again
+    #[allow(clippy::too_many_arguments)]
+    #[allow(non_snake_case)]
+    pub fn prove(
+        &self,
+        l: usize,
+        R: &[u8],
+        o: &[u8],
+        k: &[u8],
+        r: &[u8],
+        A: &[u8],
+        t: &[u8],
+        f: u64,
+        N: &str,
+        D: &[u8],
+    ) -> Result<Vec<u8>, js_sys::Error> {
+        prove_with(
+            &self.0,
+            parse_witness(l, R, o, k, r, A, t, f, parse_denomination(N)?, D)?,
+        )
+    }
+
+    ///Generate serialized proof with secrets taken from the note
+    #[allow(clippy::too_many_arguments)]
+    #[allow(non_snake_case)]
+    #[wasm_bindgen(js_name = proveFromNote)]
+    pub fn prove_from_note(
+        &self,
+        l: usize,
+        R: &[u8],
+        o: &[u8],
+        note: &str,
+        A: &[u8],
+        t: &[u8],
+        f: u64,
+        D: &[u8],
+    ) -> Result<Vec<u8>, js_sys::Error> {
+        prove_with(&self.0, parse_note_witness(l, R, o, note, A, t, f, D)?)
+    }
+}
+
 ///Generate serialized proof which is compatible with js and can be used in frontend
 #[allow(clippy::too_many_arguments)]
 #[allow(non_snake_case)]
@@ -35,35 +97,40 @@ pub fn generate_proof(
     l: usize,
     R: &[u8],
     o: &[u8],
-    k: u32,
-    r: u32,
+    k: &[u8],
+    r: &[u8],
     A: &[u8],
     t: &[u8],
     f: u64,
+    N: &str,
+    D: &[u8],
 ) -> Result<Vec<u8>, js_sys::Error> {
-    //Read opening from bytes array
-    let mut opening = [[0; 32]; DEFAULT_DEPTH];
-    for i in 0..DEFAULT_DEPTH {
-        for j in 0..32 {
-            opening[i][j] = o[i * 32 + j];
-        }
-    }
+    let witness = parse_witness(l, R, o, k, r, A, t, f, parse_denomination(N)?, D)?;
 
-    // Parse arguments
-    let R: PoseidonHash = R
-        .try_into()
-        .map_err(|err: TryFromSliceError| js_sys::Error::new(&err.to_string()))?;
-    let A = A
-        .try_into()
-        .map_err(|err: TryFromSliceError| js_sys::Error::new(&err.to_string()))?;
-    let t = t
-        .try_into()
-        .map_err(|err: TryFromSliceError| js_sys::Error::new(&err.to_string()))?;
+    let prover = SlushieProver::new(SERIALIZED_PUBLIC_PARAMETERS)?;
 
-    //Generate proof
-    prove(SERIALIZED_PUBLIC_PARAMETERS, l, R, opening, k, r, A, t, f)
-        .map_err(|err| js_sys::Error::new(&format!("{:?}", err)))
-        .map(|proof| proof.into_iter().collect())
+    prove_with(&prover, witness)
+}
+
+///Generate serialized proof with secrets taken from the note
+#[allow(clippy::too_many_arguments)]
+#[allow(non_snake_case)]
+#[wasm_bindgen]
+pub fn generate_proof_from_note(
+    l: usize,
+    R: &[u8],
+    o: &[u8],
+    note: &str,
+    A: &[u8],
+    t: &[u8],
+    f: u64,
+    D: &[u8],
+) -> Result<Vec<u8>, js_sys::Error> {
+    let witness = parse_note_witness(l, R, o, note, A, t, f, D)?;
+
+    let prover = SlushieProver::new(SERIALIZED_PUBLIC_PARAMETERS)?;
+
+    prove_with(&prover, witness)
 }
 
 ///Generate serialized proof which is compatible with js and can be used in frontend
@@ -75,13 +142,74 @@ pub fn generate_proof_with_pd(
     l: usize,
     R: &[u8],
     o: &[u8],
-    k: u32,
-    r: u32,
+    k: &[u8],
+    r: &[u8],
     A: &[u8],
     t: &[u8],
     f: u64,
+    N: &str,
+    D: &[u8],
+) -> Result<Vec<u8>, js_sys::Error> {
+    let witness = parse_witness(l, R, o, k, r, A, t, f, parse_denomination(N)?, D)?;
+
+    let prover = SlushieProver::from_prover_data(PROVER_DATA, COMMIT_KEY)?;
+
+    prove_with(&prover, witness)
+}
+
+///Generate serialized proof with included prover data and secrets taken from the note
+#[cfg(feature = "js_include_pd")]
+#[allow(clippy::too_many_arguments)]
+#[allow(non_snake_case)]
+#[wasm_bindgen]
+pub fn generate_proof_with_pd_from_note(
+    l: usize,
+    R: &[u8],
+    o: &[u8],
+    note: &str,
+    A: &[u8],
+    t: &[u8],
+    f: u64,
+    D: &[u8],
+) -> Result<Vec<u8>, js_sys::Error> {
+    let witness = parse_note_witness(l, R, o, note, A, t, f, D)?;
+
+    let prover = SlushieProver::from_prover_data(PROVER_DATA, COMMIT_KEY)?;
+
+    prove_with(&prover, witness)
+}
+
+///Generate proof with provided prover and convert it to js compatible type
+fn prove_with(
+    prover: &SlushieProver<DEFAULT_DEPTH>,
+    witness: WithdrawalWitness<DEFAULT_DEPTH>,
 ) -> Result<Vec<u8>, js_sys::Error> {
+    prover
+        .prove(&witness)
+        .map(|proof| proof.into_iter().collect())
+        .map_err(js_sys::Error::from)
+}
+
+///Parse arguments passed from js into the witness for default depth
+#[allow(clippy::too_many_arguments)]
+#[allow(non_snake_case)]
+fn parse_witness(
+    l: usize,
+    R: &[u8],
+    o: &[u8],
+    k: &[u8],
+    r: &[u8],
+    A: &[u8],
+    t: &[u8],
+    f: u64,
+    N: u128,
+    D: &[u8],
+) -> Result<WithdrawalWitness<DEFAULT_DEPTH>, js_sys::Error> {
     //Read opening from bytes array
+    if o.len() != 32 * DEFAULT_DEPTH {
+        return Err(js_sys::Error::new("Tree opening has wrong length"));
+    }
+
     let mut opening = [[0; 32]; DEFAULT_DEPTH];
     for i in 0..DEFAULT_DEPTH {
         for j in 0..32 {
@@ -93,17 +221,63 @@ pub fn generate_proof_with_pd(
     let R: PoseidonHash = R
         .try_into()
         .map_err(|err: TryFromSliceError| js_sys::Error::new(&err.to_string()))?;
+    let k = k
+        .try_into()
+        .map_err(|err: TryFromSliceError| js_sys::Error::new(&err.to_string()))?;
+    let r = r
+        .try_into()
+        .map_err(|err: TryFromSliceError| js_sys::Error::new(&err.to_string()))?;
     let A = A
         .try_into()
         .map_err(|err: TryFromSliceError| js_sys::Error::new(&err.to_string()))?;
     let t = t
         .try_into()
         .map_err(|err: TryFromSliceError| js_sys::Error::new(&err.to_string()))?;
+    let D = D
+        .try_into()
+        .map_err(|err: TryFromSliceError| js_sys::Error::new(&err.to_string()))?;
 
-    //Generate proof
-    prove_with_vd(PROVER_DATA, COMMIT_KEY, l, R, opening, k, r, A, t, f)
-        .map_err(|err| js_sys::Error::new(&format!("{:?}", err)))
-        .map(|proof| proof.into_iter().collect())
+    Ok(WithdrawalWitness {
+        l,
+        R,
+        o: opening,
+        k,
+        r,
+        A,
+        t,
+        f,
+        N,
+        D,
+    })
+}
+
+///Parse arguments passed from js into the witness, taking secrets and denomination from the note
+#[allow(clippy::too_many_arguments)]
+#[allow(non_snake_case)]
+fn parse_note_witness(
+    l: usize,
+    R: &[u8],
+    o: &[u8],
+    note: &str,
+    A: &[u8],
+    t: &[u8],
+    f: u64,
+    D: &[u8],
+) -> Result<WithdrawalWitness<DEFAULT_DEPTH>, js_sys::Error> {
+    let note = read_note(note)?;
+
+    parse_witness(
+        l,
+        R,
+        o,
+        &note.nullifier,
+        &note.randomness,
+        A,
+        t,
+        f,
+        note.denomination,
+        D,
+    )
 }
 
 /// Generate randomness, nullifier, commitment and nullifier hash
@@ -116,19 +290,85 @@ pub fn generate_commitment() -> js_sys::Array {
         nullifier_hash_bytes,
     } = commitment_gen();
 
-    // Set nullifier as js number
-    let js_nullifier = js_sys::Number::from(nullifier);
+    secrets_to_js(
+        nullifier,
+        randomness,
+        commitment_bytes,
+        nullifier_hash_bytes,
+    )
+}
+
+/// Generate domain of the withdrawal proof from the contract address and the chain identifier
+#[wasm_bindgen]
+pub fn generate_withdrawal_domain(
+    contract: &[u8],
+    chain_id: &[u8],
+) -> Result<Vec<u8>, js_sys::Error> {
+    let contract = contract
+        .try_into()
+        .map_err(|err: TryFromSliceError| js_sys::Error::new(&err.to_string()))?;
+    let chain_id = chain_id
+        .try_into()
+        .map_err(|err: TryFromSliceError| js_sys::Error::new(&err.to_string()))?;
 
-    // Set randomness as js number
-    let js_randomness = js_sys::Number::from(randomness);
+    Ok(withdrawal_domain(contract, chain_id).to_vec())
+}
+
+/// Generate note string for the deposit of provided size
+#[wasm_bindgen]
+pub fn generate_note(denomination: &str, network: &str) -> Result<String, js_sys::Error> {
+    Note::generate(parse_denomination(denomination)?, network)
+        .map(|note| note.to_string())
+        .map_err(|err| js_sys::Error::new(&err.to_string()))
+}
+
+/// Parse note string into nullifier, randomness, commitment and nullifier hash
+#[wasm_bindgen]
+pub fn parse_note(note: &str) -> Result<js_sys::Array, js_sys::Error> {
+    let note = read_note(note)?;
+
+    Ok(secrets_to_js(
+        note.nullifier,
+        note.randomness,
+        note.commitment(),
+        note.nullifier_hash(),
+    ))
+}
+
+///Parse denomination passed from js as a decimal string, since it may not fit into u64
+fn parse_denomination(denomination: &str) -> Result<u128, js_sys::Error> {
+    denomination
+        .parse::<u128>()
+        .map_err(|_| js_sys::Error::new("Denomination is not a number"))
+}
+
+///Parse note string with js compatible error
+fn read_note(note: &str) -> Result<Note, js_sys::Error> {
+    Note::parse(note).map_err(|err| js_sys::Error::new(&err.to_string()))
+}
+
+///Convert secrets to the js array of Uint8Arrays
+fn secrets_to_js(
+    nullifier: Nullifier,
+    randomness: Randomness,
+    commitment: PoseidonHash,
+    nullifier_hash: PoseidonHash,
+) -> js_sys::Array {
+    // Set nullifier as js Uint8Array
+    let js_nullifier = js_sys::Uint8Array::new_with_length(32);
+    js_nullifier.copy_from(&nullifier);
+
+    // Set randomness as js Uint8Array
+    let js_randomness = js_sys::Uint8Array::new_with_length(32);
+    js_randomness.copy_from(&randomness);
 
     // Set commitment as js Uint8Array
     let js_commitment = js_sys::Uint8Array::new_with_length(32);
-    js_commitment.copy_from(&commitment_bytes);
+    js_commitment.copy_from(&commitment);
 
     // Set nullifier hash as js Uint8Array
     let js_nullifier_hash = js_sys::Uint8Array::new_with_length(32);
-    js_nullifier_hash.copy_from(&nullifier_hash_bytes);
+    js_nullifier_hash.copy_from(&nullifier_hash);
 
     js_sys::Array::of4(
         &js_nullifier,
@@ -160,12 +400,12 @@ pub fn generate_tree_opening(
 
     // Get tree opening for leaf index
     let tree_opening = tree
-        .get_opening(leaf_index)
+        .opening(leaf_index)
         .map_err(|err: MerkleTreeError| js_sys::Error::new(&format!("{:?}", err)))?;
 
     // Get tree path for leaf index
     let tree_path = tree
-        .get_path(leaf_index)
+        .path(leaf_index)
         .map_err(|err: MerkleTreeError| js_sys::Error::new(&format!("{:?}", err)))?;
 
     // Convert to js types
@@ -183,7 +423,9 @@ pub fn generate_tree_opening(
 #[allow(non_snake_case)]
 #[cfg(test)]
 mod tests {
+    use dusk_plonk::prelude::BlsScalar;
     use dusk_poseidon::sponge;
+    use rand_core::OsRng;
     use wasm_bindgen_test::*;
 
     ///Depth which is used in Slushie mixer contract
@@ -198,25 +440,42 @@ mod tests {
     #[wasm_bindgen_test]
     fn generate_proof_test() {
         const DEPTH: usize = DEFAULT_DEPTH;
-        let k = rand::random::<u32>();
-        let r = rand::random::<u32>();
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
         let l = rand::random::<u16>() as usize;
-        let f = rand::random::<u64>();
+        // Fee doesn't exceed the denomination
+        let f = 1;
 
         let (R, o) = setup::<DEPTH>(k, r, l);
 
-        let proof = &prove_with_vd(PD, COMMIT_KEY, l, R, o, k, r, PAYOUT, RELAYER, f).unwrap();
+        let proof = &prove_with_vd(
+            &PD,
+            COMMIT_KEY,
+            l,
+            R,
+            o,
+            scalar_to_bytes(k),
+            scalar_to_bytes(r),
+            PAYOUT,
+            RELAYER,
+            f,
+            DENOMINATION,
+            DOMAIN,
+        )
+        .unwrap();
 
-        let h = sponge::hash(&[(k as u64).into()]);
+        let h = sponge::hash(&[k]);
 
         verify_with_vd(
-            VD,
+            &VD,
             OPENING_KEY,
             scalar_to_bytes(h),
             R,
             PAYOUT,
             RELAYER,
             f,
+            DENOMINATION,
+            DOMAIN,
             proof,
         )
         .unwrap();
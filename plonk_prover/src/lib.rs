@@ -2,6 +2,7 @@
 
 mod circuit;
 mod commitment_generation;
+mod error;
 pub mod hasher;
 mod proof_generation;
 mod proof_verification;
@@ -10,28 +11,42 @@ mod utils;
 #[cfg(all(feature = "proof_generator"))]
 pub mod merkle_tree;
 
+#[cfg(feature = "proof_generator")]
+pub mod note;
+
 #[macro_use]
 extern crate alloc;
 
+//Native and wasm callers of the prover have std, used for `std::error::Error` of the errors
+#[cfg(feature = "proof_generator")]
+extern crate std;
+
 #[cfg(all(feature = "proof_generator", not(feature = "js")))]
 pub mod public_parameters_generation;
 
 #[cfg(all(feature = "proof_generator", not(feature = "js")))]
 pub use proof_generation::{prove, prove_with_vd};
 
+#[cfg(feature = "proof_generator")]
+pub use proof_generation::{SlushieProver, WithdrawalWitness};
+
 #[cfg(all(feature = "proof_generator", not(feature = "js")))]
 pub use utils::index_to_path;
 
 #[cfg(all(feature = "proof_generator", not(feature = "js")))]
 pub use commitment_generation::{generate_commitment, GeneratedCommitment};
 
+pub use error::SlushieProverError;
 pub use proof_verification::*;
 
 #[cfg(feature = "js")]
 mod js;
 
 #[cfg(feature = "js")]
-pub use js::{generate_commitment, generate_proof, generate_tree_opening};
+pub use js::{
+    generate_commitment, generate_note, generate_proof, generate_proof_from_note,
+    generate_tree_opening, generate_withdrawal_domain, parse_note, JsSlushieProver,
+};
 
 /// Tests take some time due to proof generating. Recommend running them in release mode with parallel feature
 /// cargo test -r --features parallel  
@@ -47,8 +62,19 @@ mod tests {
     use shared::public_types::*;
 
     use crate::circuit::*;
-    use crate::proof_generation::{prove, prove_with_vd};
+    use crate::hasher::Poseidon;
+    use crate::merkle_tree::MerkleTree;
+    use crate::proof_generation::{prove, prove_with_vd, SlushieProver, WithdrawalWitness};
+    #[cfg(not(feature = "js"))]
+    use crate::public_parameters_generation::{
+        generate_prover_data, generate_verifier_data, generate_verifier_data_with_depth,
+    };
     use crate::utils::index_to_path;
+    use alloc::string::ToString;
+    #[cfg(not(feature = "js"))]
+    use alloc::vec::Vec;
+    #[cfg(not(feature = "js"))]
+    use lazy_static::lazy_static;
 
     use super::*;
 
@@ -71,8 +97,8 @@ mod tests {
             hex!("38c4c4c0f0e9de905b304b60f3ab77b47e2f6b4a388b7859373c6e6a1581708a");
         const RELAYER: Pubkey =
             hex!("92fba99dfb7832c4268e299efb9cd3aaad7153bbee9974729340b528d276936e");
-        let k = 3141592653;
-        let r = 1;
+        let k = BlsScalar::from(3141592653);
+        let r = BlsScalar::from(1);
         let f = 0;
         let l = 1;
 
@@ -80,10 +106,10 @@ mod tests {
         let pp = PublicParameters::setup(MAX_DEGREE, &mut OsRng).unwrap();
 
         //Calculate nullifier hash
-        let h = sponge::hash(&[(k as u64).into()]);
+        let h = sponge::hash(&[k]);
 
         //Calculate commitment
-        let commitment = sponge::hash(&[(k as u64).into(), (r as u64).into()]);
+        let commitment = sponge::hash(&[k, r]);
 
         //Calculate opening
         let mut o = [BlsScalar::zero(); DEPTH];
@@ -103,11 +129,13 @@ mod tests {
             l,
             u64_to_bytes(root.0),
             [u64_to_bytes(o[0].0), u64_to_bytes(o[1].0)],
-            k,
-            r,
+            scalar_to_bytes(k),
+            scalar_to_bytes(r),
             PAYOUT,
             RELAYER,
             f,
+            DENOMINATION,
+            DOMAIN,
         )
         .unwrap();
 
@@ -119,6 +147,8 @@ mod tests {
             PAYOUT,
             RELAYER,
             f,
+            DENOMINATION,
+            DOMAIN,
             proof,
         )
         .unwrap();
@@ -132,7 +162,7 @@ mod tests {
     ///   o[0]  hash(k || r)
     ///    0        1
     #[test]
-    #[should_panic = "ProofVerificationError"]
+    #[should_panic = "RootMismatch"]
     fn verification_error() {
         //Set depth
         const DEPTH: usize = 2;
@@ -142,8 +172,8 @@ mod tests {
             hex!("38c4c4c0f0e9de905b304b60f3ab77b47e2f6b4a388b7859373c6e6a1581708a");
         const RELAYER: Pubkey =
             hex!("92fba99dfb7832c4268e299efb9cd3aaad7153bbee9974729340b528d276936e");
-        let k = 3141592653;
-        let r = 1;
+        let k = BlsScalar::from(3141592653);
+        let r = BlsScalar::from(1);
         let f = 0;
         // Wrong index
         let l = 0;
@@ -152,10 +182,10 @@ mod tests {
         let pp = PublicParameters::setup(MAX_DEGREE, &mut OsRng).unwrap();
 
         //Calculate nullifier hash
-        let h = sponge::hash(&[(k as u64).into()]);
+        let h = sponge::hash(&[k]);
 
         //Calculate commitment
-        let commitment = sponge::hash(&[(k as u64).into(), (r as u64).into()]);
+        let commitment = sponge::hash(&[k, r]);
 
         //Calculate opening
         let mut o = [BlsScalar::zero(); DEPTH];
@@ -175,11 +205,13 @@ mod tests {
             l,
             u64_to_bytes(root.0),
             [u64_to_bytes(o[0].0), u64_to_bytes(o[1].0)],
-            k,
-            r,
+            scalar_to_bytes(k),
+            scalar_to_bytes(r),
             PAYOUT,
             RELAYER,
             f,
+            DENOMINATION,
+            DOMAIN,
         )
         .unwrap();
 
@@ -191,6 +223,8 @@ mod tests {
             PAYOUT,
             RELAYER,
             f,
+            DENOMINATION,
+            DOMAIN,
             proof,
         )
         .unwrap();
@@ -198,12 +232,12 @@ mod tests {
 
     ///Setup function for every test
     pub fn setup<const DEPTH: usize>(
-        k: u32,
-        r: u32,
+        k: BlsScalar,
+        r: BlsScalar,
         l: usize,
     ) -> (PoseidonHash, [PoseidonHash; DEPTH]) {
         //Calculate commitment
-        let commitment = sponge::hash(&[(k as u64).into(), (r as u64).into()]);
+        let commitment = sponge::hash(&[k, r]);
 
         //Calculate opening
         let (R, o) = get_opening(l, commitment);
@@ -250,18 +284,37 @@ mod tests {
 
     //Setup public parameters from file to reduce tests time
     pub const PP: &[u8] = include_bytes!("../../public-parameters/pp-test");
-    pub const VD: &[u8] = include_bytes!("../../public-parameters/vd-test");
     pub const OPENING_KEY: &[u8; OpeningKey::SIZE] =
         include_bytes!("../../public-parameters/opening-key-test");
-    pub const PD: &[u8] = include_bytes!("../../public-parameters/pd-test");
     pub const COMMIT_KEY: &[u8] = include_bytes!("../../public-parameters/commit-key-test");
 
+    #[cfg(not(feature = "js"))]
+    lazy_static! {
+        ///Prover and verifier data compiled once from the test public parameters,
+        ///so they always match the current circuit
+        pub static ref PD: Vec<u8> = generate_prover_data(PP).unwrap().0;
+        pub static ref VD: Vec<u8> = generate_verifier_data(PP).unwrap().0;
+    }
+
+    //Public parameters generation isn't available in wasm, so precompiled data is used there
+    #[cfg(feature = "js")]
+    pub const PD: &[u8] = include_bytes!("../../public-parameters/pd-test");
+    #[cfg(feature = "js")]
+    pub const VD: &[u8] = include_bytes!("../../public-parameters/vd-test");
+
     ///Set constant PAYOUT and RELAYER address
     pub const PAYOUT: Pubkey =
         hex!("38c4c4c0f0e9de905b304b60f3ab77b47e2f6b4a388b7859373c6e6a1581708a");
     pub const RELAYER: Pubkey =
         hex!("92fba99dfb7832c4268e299efb9cd3aaad7153bbee9974729340b528d276936e");
 
+    ///Set constant denomination which is more than any fee
+    pub const DENOMINATION: u128 = 1 << 64;
+
+    ///Set constant domain of the contract and the chain
+    pub const DOMAIN: PoseidonHash =
+        hex!("2478515297534ff5944224b019b82e8242b325b26624825d639c465b360aff0f");
+
     ///Depth which is used in Slushie mixer contract
     use shared::constants::DEFAULT_DEPTH;
 
@@ -274,18 +327,42 @@ mod tests {
         // Max depth
         const DEPTH: usize = MAX_DEPTH;
 
-        let k = rand::random::<u32>();
-        let r = rand::random::<u32>();
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
         let l = rand::random::<u16>() as usize;
         let f = rand::random::<u64>();
 
         let (R, o) = setup::<DEPTH>(k, r, l);
 
-        let proof = &prove(PP, l, R, o, k, r, PAYOUT, RELAYER, f).unwrap();
+        let proof = &prove(
+            PP,
+            l,
+            R,
+            o,
+            scalar_to_bytes(k),
+            scalar_to_bytes(r),
+            PAYOUT,
+            RELAYER,
+            f,
+            DENOMINATION,
+            DOMAIN,
+        )
+        .unwrap();
 
-        let h = sponge::hash(&[(k as u64).into()]);
+        let h = sponge::hash(&[k]);
 
-        verify::<DEPTH>(PP, scalar_to_bytes(h), R, PAYOUT, RELAYER, f, proof).unwrap();
+        verify::<DEPTH>(
+            PP,
+            scalar_to_bytes(h),
+            R,
+            PAYOUT,
+            RELAYER,
+            f,
+            DENOMINATION,
+            DOMAIN,
+            proof,
+        )
+        .unwrap();
     }
 
     ///Test for checking circuit works with random arguments
@@ -294,205 +371,849 @@ mod tests {
         const DEPTH: usize = DEFAULT_DEPTH;
 
         // All arguments are random
-        let k = rand::random::<u32>();
-        let r = rand::random::<u32>();
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
         let l = rand::random::<u16>() as usize;
         let f = rand::random::<u64>();
 
         let (R, o) = setup::<DEPTH>(k, r, l);
 
-        let proof = &prove_with_vd(PD, COMMIT_KEY, l, R, o, k, r, PAYOUT, RELAYER, f).unwrap();
+        let proof = &prove_with_vd(
+            &PD,
+            COMMIT_KEY,
+            l,
+            R,
+            o,
+            scalar_to_bytes(k),
+            scalar_to_bytes(r),
+            PAYOUT,
+            RELAYER,
+            f,
+            DENOMINATION,
+            DOMAIN,
+        )
+        .unwrap();
 
-        let h = sponge::hash(&[(k as u64).into()]);
+        let h = sponge::hash(&[k]);
 
         verify_with_vd(
-            VD,
+            &VD,
             OPENING_KEY,
             scalar_to_bytes(h),
             R,
             PAYOUT,
             RELAYER,
             f,
+            DENOMINATION,
+            DOMAIN,
             proof,
         )
         .unwrap();
     }
 
-    ///Test for checking circuit works with wrong fee
+    ///Test for checking one prover can generate many proofs without recompiling circuit
     #[test]
-    #[should_panic = "WrongIndex"]
-    fn wrong_index() {
-        const DEPTH: usize = 3;
-        let k = rand::random::<u32>();
-        let r = rand::random::<u32>();
-        // Index is more than can be in tree with depth = 3
-        let l = 8;
+    fn prover_reused_for_many_proofs() {
+        const DEPTH: usize = DEFAULT_DEPTH;
+
+        let prover = SlushieProver::<DEPTH>::from_prover_data(&PD, COMMIT_KEY).unwrap();
+
+        for _ in 0..2 {
+            let k = BlsScalar::random(&mut OsRng);
+            let r = BlsScalar::random(&mut OsRng);
+            let l = rand::random::<u16>() as usize;
+            let f = rand::random::<u64>();
+
+            let (R, o) = setup::<DEPTH>(k, r, l);
+
+            let proof = &prover
+                .prove(&WithdrawalWitness {
+                    l,
+                    R,
+                    o,
+                    k: scalar_to_bytes(k),
+                    r: scalar_to_bytes(r),
+                    A: PAYOUT,
+                    t: RELAYER,
+                    f,
+                    N: DENOMINATION,
+                    D: DOMAIN,
+                })
+                .unwrap();
+
+            let h = sponge::hash(&[k]);
+
+            verify_with_vd(
+                &VD,
+                OPENING_KEY,
+                scalar_to_bytes(h),
+                R,
+                PAYOUT,
+                RELAYER,
+                f,
+                DENOMINATION,
+                DOMAIN,
+                proof,
+            )
+            .unwrap();
+        }
+    }
+
+    ///Test for checking proof of the prover is accepted by the verifier,
+    ///which places public inputs at the gates of the compiled circuit
+    #[test]
+    fn prover_and_verifier_round_trip() {
+        const DEPTH: usize = DEFAULT_DEPTH;
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
+        let l = rand::random::<u16>() as usize;
         let f = rand::random::<u64>();
 
         let (R, o) = setup::<DEPTH>(k, r, l);
 
-        let proof = &prove(PP, l, R, o, k, r, PAYOUT, RELAYER, f).unwrap();
+        let proof = SlushieProver::<DEPTH>::new(PP)
+            .unwrap()
+            .prove(&WithdrawalWitness {
+                l,
+                R,
+                o,
+                k: scalar_to_bytes(k),
+                r: scalar_to_bytes(r),
+                A: PAYOUT,
+                t: RELAYER,
+                f,
+                N: DENOMINATION,
+                D: DOMAIN,
+            })
+            .unwrap();
+
+        let mut public_inputs = ProofPublicInputs {
+            nullifier_hash: scalar_to_bytes(sponge::hash(&[k])),
+            root: R,
+            recipient: PAYOUT,
+            relayer: RELAYER,
+            fee: f,
+            denomination: DENOMINATION,
+            domain: DOMAIN,
+        };
+
+        // Verifier compiled from public parameters and verifier loaded from verifier data,
+        // as in the contract and the relayer, accept the proof
+        let verifiers = [
+            SlushieVerifier::from_public_parameters::<DEPTH>(PP).unwrap(),
+            SlushieVerifier::new(&VD, OPENING_KEY).unwrap(),
+        ];
+        for verifier in &verifiers {
+            verifier.verify(&public_inputs, &proof).unwrap();
+        }
+
+        // and reject it for another recipient
+        public_inputs.recipient = RELAYER;
+        for verifier in &verifiers {
+            assert!(verifier.verify(&public_inputs, &proof).is_err());
+        }
+    }
+
+    ///Test for checking verifier data tells the depth which the circuit is compiled for
+    #[test]
+    #[cfg(not(feature = "js"))]
+    fn verifier_depth() {
+        let verifier = SlushieVerifier::new(&VD, OPENING_KEY).unwrap();
+        assert_eq!(verifier.depth(), Some(DEFAULT_DEPTH));
+
+        let (vd, opening_key) = generate_verifier_data_with_depth::<2>(PP).unwrap();
+        let verifier = SlushieVerifier::new(&vd, &opening_key).unwrap();
+        assert_eq!(verifier.depth(), Some(2));
+    }
+
+    ///Test for checking batch verification returns a result for every proof
+    #[test]
+    fn verifier_checks_batch() {
+        const DEPTH: usize = DEFAULT_DEPTH;
 
-        let h = sponge::hash(&[(k as u64).into()]);
+        let prover = SlushieProver::<DEPTH>::from_prover_data(&PD, COMMIT_KEY).unwrap();
+        let verifier = SlushieVerifier::new(&VD, OPENING_KEY).unwrap();
+
+        let mut batch = vec![];
+        for _ in 0..3 {
+            let k = BlsScalar::random(&mut OsRng);
+            let r = BlsScalar::random(&mut OsRng);
+            let l = rand::random::<u16>() as usize;
+            let f = rand::random::<u64>();
+
+            let (R, o) = setup::<DEPTH>(k, r, l);
+
+            let proof = prover
+                .prove(&WithdrawalWitness {
+                    l,
+                    R,
+                    o,
+                    k: scalar_to_bytes(k),
+                    r: scalar_to_bytes(r),
+                    A: PAYOUT,
+                    t: RELAYER,
+                    f,
+                    N: DENOMINATION,
+                    D: DOMAIN,
+                })
+                .unwrap();
+
+            let public_inputs = ProofPublicInputs {
+                nullifier_hash: scalar_to_bytes(sponge::hash(&[k])),
+                root: R,
+                recipient: PAYOUT,
+                relayer: RELAYER,
+                fee: f,
+                denomination: DENOMINATION,
+                domain: DOMAIN,
+            };
 
-        verify::<DEPTH>(PP, scalar_to_bytes(h), R, PAYOUT, RELAYER, f, proof).unwrap();
+            batch.push((public_inputs, proof));
+        }
+
+        // Fee of the last proof is incorrect
+        batch[2].0.fee = batch[2].0.fee.wrapping_add(1);
+
+        let results = verifier.verify_batch(&batch);
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_ok());
+        assert!(results[2].is_err());
     }
 
     ///Test for checking circuit works with wrong fee
     #[test]
-    #[should_panic = "ProofVerificationError"]
-    fn wrong_fee() {
-        const DEPTH: usize = DEFAULT_DEPTH;
-        let k = rand::random::<u32>();
-        let r = rand::random::<u32>();
-        let l = rand::random::<u16>() as usize;
+    #[should_panic = "WrongIndex"]
+    fn wrong_index() {
+        const DEPTH: usize = 3;
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
+        // Index is more than can be in tree with depth = 3
+        let l = 8;
         let f = rand::random::<u64>();
 
         let (R, o) = setup::<DEPTH>(k, r, l);
 
-        let proof = &prove_with_vd(PD, COMMIT_KEY, l, R, o, k, r, PAYOUT, RELAYER, f).unwrap();
+        let proof = &prove(
+            PP,
+            l,
+            R,
+            o,
+            scalar_to_bytes(k),
+            scalar_to_bytes(r),
+            PAYOUT,
+            RELAYER,
+            f,
+            DENOMINATION,
+            DOMAIN,
+        )
+        .unwrap();
 
-        let h = sponge::hash(&[(k as u64).into()]);
+        let h = sponge::hash(&[k]);
 
-        verify_with_vd(
-            VD,
-            OPENING_KEY,
+        verify::<DEPTH>(
+            PP,
             scalar_to_bytes(h),
             R,
             PAYOUT,
             RELAYER,
-            // Fee is incorrect
-            f + 1,
+            f,
+            DENOMINATION,
+            DOMAIN,
             proof,
         )
-        .expect("ProofVerificationError");
+        .unwrap();
     }
 
-    ///Test for checking circuit works with wrong nullifier hash
-    #[test]
-    #[should_panic = "ProofVerificationError"]
-    fn wrong_nullifier_hash() {
-        const DEPTH: usize = DEFAULT_DEPTH;
-        let k = rand::random::<u32>();
-        let r = rand::random::<u32>();
+    ///Proof for a random commitment and public inputs which it is verified with
+    fn valid_proof() -> (ProofPublicInputs, SerializedProof) {
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
         let l = rand::random::<u16>() as usize;
         let f = rand::random::<u64>();
 
-        let (R, o) = setup::<DEPTH>(k, r, l);
-
-        let proof = &prove_with_vd(PD, COMMIT_KEY, l, R, o, k, r, PAYOUT, RELAYER, f).unwrap();
+        let (R, o) = setup::<DEFAULT_DEPTH>(k, r, l);
 
-        verify_with_vd(
-            VD,
-            OPENING_KEY,
-            // Nullifier hash in public inputs is incorrect
-            scalar_to_bytes(BlsScalar::zero()),
+        let proof = prove_with_vd(
+            &PD,
+            COMMIT_KEY,
+            l,
             R,
+            o,
+            scalar_to_bytes(k),
+            scalar_to_bytes(r),
             PAYOUT,
             RELAYER,
             f,
-            proof,
+            DENOMINATION,
+            DOMAIN,
         )
-        .expect("ProofVerificationError");
+        .unwrap();
+
+        let public_inputs = ProofPublicInputs {
+            nullifier_hash: scalar_to_bytes(sponge::hash(&[k])),
+            root: R,
+            recipient: PAYOUT,
+            relayer: RELAYER,
+            fee: f,
+            denomination: DENOMINATION,
+            domain: DOMAIN,
+        };
+
+        (public_inputs, proof)
     }
 
-    ///Test for checking circuit works with wrong relayer
+    ///Change of the public inputs made after the proof is generated
+    type PublicInputsChange = fn(&mut ProofPublicInputs);
+
+    ///Test for checking proof can't be verified when any of its public inputs is changed
     #[test]
-    #[should_panic = "ProofVerificationError"]
-    fn wrong_relayer() {
+    fn wrong_public_inputs() {
+        let changes: [(&str, PublicInputsChange); 8] = [
+            ("fee", |inputs| inputs.fee = inputs.fee.wrapping_add(1)),
+            ("nullifier hash", |inputs| {
+                inputs.nullifier_hash = scalar_to_bytes(BlsScalar::zero())
+            }),
+            ("relayer", |inputs| inputs.relayer = PAYOUT),
+            ("payout", |inputs| inputs.recipient = RELAYER),
+            // Another account with the same reduction modulo the field order
+            ("payout modulo field order", |inputs| {
+                inputs.recipient = add_modulus(PAYOUT)
+            }),
+            ("root", |inputs| inputs.root = [0; 32]),
+            // Proof for one pool can't be used in another
+            ("denomination", |inputs| {
+                inputs.denomination = DENOMINATION * 10
+            }),
+            // Proof for one contract or chain can't be used in another
+            ("domain", |inputs| {
+                inputs.domain = withdrawal_domain(PAYOUT, [2; 32])
+            }),
+        ];
+
+        let verifier = SlushieVerifier::new(&VD, OPENING_KEY).unwrap();
+        let (public_inputs, proof) = valid_proof();
+        verifier.verify(&public_inputs, &proof).unwrap();
+
+        for (input, change) in changes {
+            let mut wrong_inputs = ProofPublicInputs { ..public_inputs };
+            change(&mut wrong_inputs);
+
+            assert!(
+                matches!(
+                    verifier.verify(&wrong_inputs, &proof),
+                    Err(SlushieProverError::ProofSystem(
+                        Error::ProofVerificationError
+                    ))
+                ),
+                "proof is verified with wrong {}",
+                input
+            );
+        }
+    }
+
+    ///Test for checking fee larger than denomination is reported before proving
+    #[test]
+    fn fee_exceeding_denomination() {
         const DEPTH: usize = DEFAULT_DEPTH;
-        let k = rand::random::<u32>();
-        let r = rand::random::<u32>();
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
         let l = rand::random::<u16>() as usize;
-        let f = rand::random::<u64>();
+        let n = rand::random::<u32>() as u128;
+        // Fee is larger than denomination
+        let f = n as u64 + 1;
 
         let (R, o) = setup::<DEPTH>(k, r, l);
 
-        let proof = &prove_with_vd(PD, COMMIT_KEY, l, R, o, k, r, PAYOUT, RELAYER, f).unwrap();
-
-        let h = sponge::hash(&[(k as u64).into()]);
-
-        verify_with_vd(
-            VD,
-            OPENING_KEY,
-            scalar_to_bytes(h),
+        let res = prove_with_vd(
+            &PD,
+            COMMIT_KEY,
+            l,
             R,
+            o,
+            scalar_to_bytes(k),
+            scalar_to_bytes(r),
             PAYOUT,
-            // Relayer in public inputs is incorrect
-            PAYOUT,
+            RELAYER,
             f,
-            proof,
-        )
-        .expect("ProofVerificationError");
+            n,
+            DOMAIN,
+        );
+        assert!(matches!(
+            res,
+            Err(SlushieProverError::FeeExceedsDenomination)
+        ));
     }
 
-    ///Test for checking circuit works with wrong payout
+    ///Test for checking proof with fee larger than denomination can't be verified
     #[test]
     #[should_panic = "ProofVerificationError"]
-    fn wrong_payout() {
+    fn fee_exceeding_denomination_in_circuit() {
+        let mut circuit = honest_circuit::<2>(1);
+        circuit.N = BlsScalar::from(100);
+        circuit.f = BlsScalar::from(101);
+
+        prove_and_verify(circuit).expect("ProofVerificationError");
+    }
+
+    ///Test for checking circuit works with fee equal to denomination
+    #[test]
+    fn fee_equal_to_denomination() {
         const DEPTH: usize = DEFAULT_DEPTH;
-        let k = rand::random::<u32>();
-        let r = rand::random::<u32>();
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
         let l = rand::random::<u16>() as usize;
         let f = rand::random::<u64>();
 
         let (R, o) = setup::<DEPTH>(k, r, l);
 
-        let proof = &prove_with_vd(PD, COMMIT_KEY, l, R, o, k, r, PAYOUT, RELAYER, f).unwrap();
+        let proof = &prove_with_vd(
+            &PD,
+            COMMIT_KEY,
+            l,
+            R,
+            o,
+            scalar_to_bytes(k),
+            scalar_to_bytes(r),
+            PAYOUT,
+            RELAYER,
+            f,
+            f as u128,
+            DOMAIN,
+        )
+        .unwrap();
 
-        let h = sponge::hash(&[(k as u64).into()]);
+        let h = sponge::hash(&[k]);
 
         verify_with_vd(
-            VD,
+            &VD,
             OPENING_KEY,
             scalar_to_bytes(h),
             R,
-            // Payout in public inputs is incorrect
-            RELAYER,
+            PAYOUT,
             RELAYER,
             f,
+            f as u128,
+            DOMAIN,
             proof,
         )
-        .expect("ProofVerificationError");
+        .unwrap();
     }
 
-    ///Test for checking circuit works with wrong root
+    ///Fill circuit with honest witness for a random commitment at leaf index `l`
+    fn honest_circuit<const DEPTH: usize>(l: usize) -> SlushieCircuit<DEPTH> {
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
+        let (R, o) = setup::<DEPTH>(k, r, l);
+
+        SlushieCircuit::<DEPTH> {
+            k,
+            r,
+            o: crate::utils::Array(o),
+            p: crate::utils::Array(index_to_path(l).unwrap()),
+            R: bytes_to_scalar(R),
+            h: sponge::hash(&[k]),
+            A: account_to_scalars(PAYOUT),
+            t: account_to_scalars(RELAYER),
+            f: BlsScalar::zero(),
+            N: u128_to_scalar(DENOMINATION),
+            D: bytes_to_scalar(DOMAIN),
+        }
+    }
+
+    ///Compute root the same way as the circuit does, for any path values
+    fn path_root<const DEPTH: usize>(circuit: &SlushieCircuit<DEPTH>) -> BlsScalar {
+        let mut current_hash = sponge::hash(&[circuit.k, circuit.r]);
+        for i in 0..DEPTH {
+            let bit = BlsScalar::from(circuit.p.0[i] as u64);
+            let sister_hash = bytes_to_scalar(circuit.o.0[i]);
+
+            let left = bit * sister_hash + (BlsScalar::one() - bit) * current_hash;
+            let right = bit * current_hash + (BlsScalar::one() - bit) * sister_hash;
+
+            current_hash = sponge::hash(&[left, right]);
+        }
+
+        current_hash
+    }
+
+    ///Prove hand-crafted circuit directly, bypassing the witness checks of the prover API,
+    ///and verify it with its own public inputs
+    fn prove_and_verify<const DEPTH: usize>(
+        mut circuit: SlushieCircuit<DEPTH>,
+    ) -> Result<(), Error> {
+        let pp = PublicParameters::setup(MAX_DEGREE, &mut OsRng).unwrap();
+        let (pk, vd) = SlushieCircuit::<DEPTH>::default().compile(&pp)?;
+
+        let proof = circuit.prove(&pp, &pk, TRANSCRIPT_INIT, &mut OsRng)?;
+
+        SlushieCircuit::<DEPTH>::verify(&pp, &vd, &proof, &circuit.public_inputs(), TRANSCRIPT_INIT)
+    }
+
+    ///Test for checking hand-crafted honest circuit works
+    #[test]
+    fn honest_circuit_works() {
+        let circuit = honest_circuit::<2>(1);
+        assert_eq!(path_root(&circuit), circuit.R);
+
+        prove_and_verify(circuit).unwrap();
+    }
+
+    ///Test for checking fee which wraps around the field modulus can't be used
+    ///to make the recipient payout fit into 128 bits
     #[test]
     #[should_panic = "ProofVerificationError"]
-    fn wrong_root() {
-        const DEPTH: usize = DEFAULT_DEPTH;
-        let k = rand::random::<u32>();
-        let r = rand::random::<u32>();
+    fn fee_wrapped_around_modulus() {
+        let mut circuit = honest_circuit::<2>(1);
+        circuit.N = BlsScalar::from(100);
+        // N - f = 200, but f doesn't fit into 64 bits
+        circuit.f = circuit.N - BlsScalar::from(200);
+
+        prove_and_verify(circuit).expect("ProofVerificationError");
+    }
+
+    ///Test for checking path bit which is neither 0 nor 1 can't be used
+    ///to select an arbitrary pair of children
+    #[test]
+    #[should_panic = "ProofVerificationError"]
+    fn non_boolean_path_bit() {
+        let mut circuit = honest_circuit::<2>(1);
+        // Select gives 2 * sister - current and 2 * current - sister
+        circuit.p.0[0] = 2;
+        circuit.R = path_root(&circuit);
+
+        prove_and_verify(circuit).expect("ProofVerificationError");
+    }
+
+    ///Test for checking nullifier which doesn't match nullifier hash can't be used
+    #[test]
+    #[should_panic = "ProofVerificationError"]
+    fn forged_nullifier_hash() {
+        let mut circuit = honest_circuit::<2>(1);
+        circuit.h = BlsScalar::random(&mut OsRng);
+
+        prove_and_verify(circuit).expect("ProofVerificationError");
+    }
+
+    ///Test for checking non-canonical nullifier hash is rejected by the verifier,
+    ///so the same nullifier can't be spent twice with another encoding
+    #[test]
+    fn non_canonical_public_inputs() {
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
         let l = rand::random::<u16>() as usize;
         let f = rand::random::<u64>();
 
-        let (R, o) = setup::<DEPTH>(k, r, l);
+        let (R, o) = setup::<DEFAULT_DEPTH>(k, r, l);
+
+        let proof = &prove_with_vd(
+            &PD,
+            COMMIT_KEY,
+            l,
+            R,
+            o,
+            scalar_to_bytes(k),
+            scalar_to_bytes(r),
+            PAYOUT,
+            RELAYER,
+            f,
+            DENOMINATION,
+            DOMAIN,
+        )
+        .unwrap();
 
-        let proof = &prove_with_vd(PD, COMMIT_KEY, l, R, o, k, r, PAYOUT, RELAYER, f).unwrap();
+        // Same nullifier hash shifted by the modulus
+        let h = scalar_to_bytes(sponge::hash(&[k]));
+        let non_canonical_h = add_modulus(h);
+
+        let verify_h = |h| {
+            verify_with_vd(
+                &VD,
+                OPENING_KEY,
+                h,
+                R,
+                PAYOUT,
+                RELAYER,
+                f,
+                DENOMINATION,
+                DOMAIN,
+                proof,
+            )
+        };
+
+        assert!(verify_h(h).is_ok());
+        assert!(matches!(
+            verify_h(non_canonical_h),
+            Err(SlushieProverError::InvalidPublicInputs)
+        ));
+
+        // Non-canonical root can't be used to generate proof
+        let res = prove_with_vd(
+            &PD,
+            COMMIT_KEY,
+            l,
+            [0xff; 32],
+            o,
+            scalar_to_bytes(k),
+            scalar_to_bytes(r),
+            PAYOUT,
+            RELAYER,
+            f,
+            DENOMINATION,
+            DOMAIN,
+        );
+        assert!(matches!(res, Err(SlushieProverError::InvalidPublicInputs)));
+    }
 
-        let h = sponge::hash(&[(k as u64).into()]);
+    ///Witness with random secrets, which opening leads to the root
+    fn valid_witness() -> WithdrawalWitness<DEFAULT_DEPTH> {
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
+        let l = rand::random::<u16>() as usize;
+        let (R, o) = setup::<DEFAULT_DEPTH>(k, r, l);
 
-        verify_with_vd(
-            VD,
+        WithdrawalWitness {
+            l,
+            R,
+            o,
+            k: scalar_to_bytes(k),
+            r: scalar_to_bytes(r),
+            A: PAYOUT,
+            t: RELAYER,
+            f: 1,
+            N: DENOMINATION,
+            D: DOMAIN,
+        }
+    }
+
+    ///Test for checking leaf index out of the tree is reported before proving
+    #[test]
+    fn check_witness_rejects_index_out_of_tree() {
+        let res = WithdrawalWitness {
+            l: 1 << DEFAULT_DEPTH,
+            ..valid_witness()
+        }
+        .check_witness();
+        assert!(matches!(res, Err(SlushieProverError::InvalidIndex)));
+    }
+
+    ///Test for checking fee larger than denomination is reported by the witness check
+    #[test]
+    fn check_witness_rejects_fee_exceeding_denomination() {
+        let res = WithdrawalWitness {
+            f: 101,
+            N: 100,
+            ..valid_witness()
+        }
+        .check_witness();
+        assert!(matches!(
+            res,
+            Err(SlushieProverError::FeeExceedsDenomination)
+        ));
+    }
+
+    ///Test for checking secrets are accepted only in canonical encoding
+    #[test]
+    fn check_witness_rejects_non_canonical_secret() {
+        let witness = valid_witness();
+
+        // Same nullifier or randomness shifted by the modulus
+        let res = WithdrawalWitness {
+            k: add_modulus(witness.k),
+            ..witness
+        }
+        .check_witness();
+        assert!(matches!(res, Err(SlushieProverError::InvalidSecret)));
+
+        let res = WithdrawalWitness {
+            r: add_modulus(witness.r),
+            ..witness
+        }
+        .check_witness();
+        assert!(matches!(res, Err(SlushieProverError::InvalidSecret)));
+    }
+
+    ///Test for checking witness check reports the input which is wrong
+    #[test]
+    fn check_witness_reports_wrong_input() {
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
+        let commitment = scalar_to_bytes(sponge::hash(&[k, r]));
+
+        // The commitment is the last leaf, so its right sister and the right sisters above are empty
+        let mut tree = MerkleTree::<DEFAULT_DEPTH, Poseidon>::new().unwrap();
+        tree.append([1; 32]).unwrap();
+        tree.append([2; 32]).unwrap();
+        let l = tree.append(commitment).unwrap();
+        let o = tree.opening(l).unwrap();
+
+        let witness = || WithdrawalWitness {
+            l,
+            R: tree.root(),
+            o,
+            k: scalar_to_bytes(k),
+            r: scalar_to_bytes(r),
+            ..valid_witness()
+        };
+        witness().check_witness().unwrap();
+
+        let check =
+            |witness: WithdrawalWitness<DEFAULT_DEPTH>| witness.check_witness().unwrap_err();
+
+        // Leaf index out of the tree
+        assert!(matches!(
+            check(WithdrawalWitness {
+                l: 1 << DEFAULT_DEPTH,
+                ..witness()
+            }),
+            SlushieProverError::InvalidIndex
+        ));
+
+        // Leaf index of the empty leaf after the commitment, its left sister is empty
+        assert!(matches!(
+            check(WithdrawalWitness {
+                l: l + 1,
+                ..witness()
+            }),
+            SlushieProverError::PathMismatch
+        ));
+
+        // Opening with a filled right sister above an empty one
+        let mut wrong_opening = o;
+        wrong_opening[2] = [3; 32];
+        assert!(matches!(
+            check(WithdrawalWitness {
+                o: wrong_opening,
+                ..witness()
+            }),
+            SlushieProverError::PathMismatch
+        ));
+
+        // Opening which is not encoded canonically
+        let mut malformed_opening = o;
+        malformed_opening[1] = [0xff; 32];
+        assert!(matches!(
+            check(WithdrawalWitness {
+                o: malformed_opening,
+                ..witness()
+            }),
+            SlushieProverError::InvalidOpening
+        ));
+
+        // Randomness which is not a canonical field element
+        assert!(matches!(
+            check(WithdrawalWitness {
+                r: [0xff; 32],
+                ..witness()
+            }),
+            SlushieProverError::InvalidSecret
+        ));
+
+        // Root which is not encoded canonically
+        assert!(matches!(
+            check(WithdrawalWitness {
+                R: [0xff; 32],
+                ..witness()
+            }),
+            SlushieProverError::InvalidPublicInputs
+        ));
+
+        // Secrets of another commitment
+        assert!(matches!(
+            check(WithdrawalWitness {
+                r: scalar_to_bytes(r + BlsScalar::one()),
+                ..witness()
+            }),
+            SlushieProverError::RootMismatch
+        ));
+
+        // Root of another tree
+        assert!(matches!(
+            check(WithdrawalWitness {
+                R: scalar_to_bytes(BlsScalar::one()),
+                ..witness()
+            }),
+            SlushieProverError::RootMismatch
+        ));
+    }
+
+    ///Test for checking callers can tell a bad key from a bad index
+    #[test]
+    fn typed_errors() {
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
+        let (R, o) = setup::<DEFAULT_DEPTH>(k, r, 0);
+
+        let prove_at = |l| {
+            prove_with_vd(
+                &PD,
+                COMMIT_KEY,
+                l,
+                R,
+                o,
+                scalar_to_bytes(k),
+                scalar_to_bytes(r),
+                PAYOUT,
+                RELAYER,
+                0,
+                DENOMINATION,
+                DOMAIN,
+            )
+        };
+
+        // Index is more than can be in tree with default depth
+        let err = prove_at(1 << DEFAULT_DEPTH).unwrap_err();
+        assert!(matches!(err, SlushieProverError::InvalidIndex));
+        assert_eq!(err.to_string(), "Leaf index doesn't fit into the tree");
+
+        assert!(matches!(
+            SlushieProver::<DEFAULT_DEPTH>::from_prover_data(&[1, 2, 3], COMMIT_KEY),
+            Err(SlushieProverError::KeyDeserialization)
+        ));
+        assert!(matches!(
+            SlushieVerifier::new(&[1, 2, 3], OPENING_KEY),
+            Err(SlushieProverError::KeyDeserialization)
+        ));
+
+        // Proof for the valid witness can't be checked with other public inputs
+        let proof = prove_at(0).unwrap();
+        let res = verify_with_vd(
+            &VD,
             OPENING_KEY,
-            scalar_to_bytes(h),
-            // Root in public inputs is incorrect
-            [0; 32],
+            scalar_to_bytes(sponge::hash(&[k])),
+            R,
             PAYOUT,
             RELAYER,
-            f,
-            proof,
-        )
-        .expect("ProofVerificationError");
+            1,
+            DENOMINATION,
+            DOMAIN,
+            &proof,
+        );
+        assert!(matches!(
+            res,
+            Err(SlushieProverError::ProofSystem(
+                Error::ProofVerificationError
+            ))
+        ));
     }
 
-    ///Test for checking circuit works with wrong opening
+    ///Test for checking domain depends on both the contract and the chain
+    #[test]
+    fn withdrawal_domain_is_unique() {
+        let domain = withdrawal_domain(PAYOUT, [1; 32]);
+
+        assert_eq!(domain, withdrawal_domain(PAYOUT, [1; 32]));
+        assert_ne!(domain, withdrawal_domain(RELAYER, [1; 32]));
+        assert_ne!(domain, withdrawal_domain(PAYOUT, [2; 32]));
+    }
+
+    ///Test for checking wrong opening is reported before proving
     #[test]
-    #[should_panic = "ProofVerificationError"]
     fn wrong_opening_with_small_depth() {
         const DEPTH: usize = 2;
-        let k = rand::random::<u32>();
-        let r = rand::random::<u32>();
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
         let l = 1usize;
         let f = rand::random::<u64>();
 
@@ -501,21 +1222,28 @@ mod tests {
         // Opening is incorrect
         o[1] = [0; 32];
 
-        let proof = &prove(PP, l, R, o, k, r, PAYOUT, RELAYER, f).unwrap();
-
-        let h = sponge::hash(&[(k as u64).into()]);
-
-        verify::<DEPTH>(PP, scalar_to_bytes(h), R, PAYOUT, RELAYER, f, proof)
-            .expect("ProofVerificationError");
+        let res = prove(
+            PP,
+            l,
+            R,
+            o,
+            scalar_to_bytes(k),
+            scalar_to_bytes(r),
+            PAYOUT,
+            RELAYER,
+            f,
+            DENOMINATION,
+            DOMAIN,
+        );
+        assert!(matches!(res, Err(SlushieProverError::RootMismatch)));
     }
 
-    ///Test for checking circuit works with wrong opening
+    ///Test for checking wrong opening is reported before proving with the biggest tree
     #[test]
-    #[should_panic = "PolynomialDegreeTooLarge"]
     fn wrong_opening_with_big_depth() {
         const DEPTH: usize = MAX_DEPTH;
-        let k = rand::random::<u32>();
-        let r = rand::random::<u32>();
+        let k = BlsScalar::random(&mut OsRng);
+        let r = BlsScalar::random(&mut OsRng);
         let l = rand::random::<u16>() as usize;
         let f = rand::random::<u64>();
 
@@ -524,14 +1252,19 @@ mod tests {
         // Opening is incorrect
         o[10] = [0; 32];
 
-        // For generating proof with incorrect opening, circuit needs more degree and time,
-        // but ProofVerificationError will be in result. To decrease tests time we use
-        // PolynomialDegreeTooLarge error during generating proof with incorrect data
-        let proof =
-            &prove(PP, l, R, o, k, r, PAYOUT, RELAYER, f).expect("PolynomialDegreeTooLarge");
-
-        let h = sponge::hash(&[(k as u64).into()]);
-
-        verify::<DEPTH>(PP, scalar_to_bytes(h), R, PAYOUT, RELAYER, f, proof).unwrap();
+        let res = prove(
+            PP,
+            l,
+            R,
+            o,
+            scalar_to_bytes(k),
+            scalar_to_bytes(r),
+            PAYOUT,
+            RELAYER,
+            f,
+            DENOMINATION,
+            DOMAIN,
+        );
+        assert!(matches!(res, Err(SlushieProverError::RootMismatch)));
     }
 }
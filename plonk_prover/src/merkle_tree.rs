@@ -2,29 +2,112 @@
 
 use crate::hasher::MerkleTreeHasher;
 use crate::utils::index_to_path;
+use shared::constants::MAX_DEPTH;
 
 use alloc::vec::Vec;
+
+/// Append-only sparse Merkle tree
+///
+/// A node without any leaf below it equals `Hash::ZEROS` for its level, so every layer
+/// stores only its filled nodes and the tree is updated only along the path of a new leaf.
+/// Gives the same roots as the Merkle tree in the Slushie contract
 pub struct MerkleTree<const DEPTH: usize, Hash: MerkleTreeHasher> {
-    /// Merkle tree nodes
-    pub layers: Vec<Vec<Hash::Output>>,
+    /// Non-zero nodes of every layer, from leaves up to the root
+    layers: Vec<Vec<Hash::Output>>,
+    /// Roots history, starting with the root of the empty tree
+    roots: Vec<Hash::Output>,
 }
 
 impl<const DEPTH: usize, Hash: MerkleTreeHasher> MerkleTree<DEPTH, Hash> {
-    pub fn get_opening(&self, leaf_index: usize) -> Result<[Hash::Output; DEPTH], MerkleTreeError> {
-        let mut result = [Default::default(); DEPTH];
+    /// Create empty merkle tree
+    pub fn new() -> Result<Self, MerkleTreeError> {
+        if DEPTH > MAX_DEPTH {
+            return Err(MerkleTreeError::DepthTooLong);
+        }
+
+        if DEPTH == 0 {
+            return Err(MerkleTreeError::DepthIsZero);
+        }
+
+        Ok(Self {
+            layers: vec![Vec::new(); DEPTH + 1],
+            // Same initial root as in the contract
+            roots: vec![Hash::ZEROS[DEPTH - 1]],
+        })
+    }
+
+    /// Number of appended leaves
+    pub fn len(&self) -> usize {
+        self.layers[0].len()
+    }
+
+    /// Check that no leaves are appended
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append leaf to the tree and return its index
+    pub fn append(&mut self, leaf: Hash::Output) -> Result<usize, MerkleTreeError> {
+        let leaf_index = self.len();
+
+        if leaf_index == 1 << DEPTH {
+            return Err(MerkleTreeError::MerkleTreeIsFull);
+        }
+
+        self.layers[0].push(leaf);
 
         let mut current_index = leaf_index;
+        let mut current_hash = leaf;
+
+        // Recompute nodes on the path from the new leaf to the root
+        for i in 0..DEPTH {
+            current_hash = if current_index % 2 == 0 {
+                Hash::hash_left_right(current_hash, Hash::ZEROS[i])
+            } else {
+                Hash::hash_left_right(self.layers[i][current_index - 1], current_hash)
+            };
+            current_index /= 2;
 
-        for (i, elem) in result.iter_mut().enumerate().take(DEPTH) {
-            if current_index % 2 == 0 {
-                *elem = *self.layers[i]
-                    .get(current_index + 1)
-                    .ok_or(MerkleTreeError::WrongLeafIndex)?;
+            let layer = &mut self.layers[i + 1];
+            if current_index == layer.len() {
+                layer.push(current_hash);
             } else {
-                *elem = *self.layers[i]
-                    .get(current_index - 1)
-                    .ok_or(MerkleTreeError::WrongLeafIndex)?;
+                layer[current_index] = current_hash;
             }
+        }
+
+        self.roots.push(current_hash);
+
+        Ok(leaf_index)
+    }
+
+    /// Get last root hash
+    pub fn root(&self) -> Hash::Output {
+        self.roots[self.roots.len() - 1]
+    }
+
+    /// Get all roots in the order they appeared, starting with the root of the empty tree
+    pub fn roots(&self) -> &[Hash::Output] {
+        &self.roots
+    }
+
+    /// Check existing provided root in roots history
+    pub fn is_known_root(&self, root: Hash::Output) -> bool {
+        self.roots.contains(&root)
+    }
+
+    /// Get value of sister nodes on the way from the leaf to the root
+    pub fn opening(&self, leaf_index: usize) -> Result<[Hash::Output; DEPTH], MerkleTreeError> {
+        if leaf_index >= self.len() {
+            return Err(MerkleTreeError::WrongLeafIndex);
+        }
+
+        let mut result = [Default::default(); DEPTH];
+
+        let mut current_index = leaf_index;
+
+        for (i, elem) in result.iter_mut().enumerate() {
+            *elem = self.node(i, current_index ^ 1);
 
             current_index >>= 1;
         }
@@ -32,9 +115,22 @@ impl<const DEPTH: usize, Hash: MerkleTreeHasher> MerkleTree<DEPTH, Hash> {
         Ok(result)
     }
 
-    pub fn get_path(&self, leaf_index: usize) -> Result<[u8; DEPTH], MerkleTreeError> {
+    /// Get path from the leaf to the root
+    pub fn path(&self, leaf_index: usize) -> Result<[u8; DEPTH], MerkleTreeError> {
+        if leaf_index >= self.len() {
+            return Err(MerkleTreeError::WrongLeafIndex);
+        }
+
         index_to_path(leaf_index).map_err(|_| MerkleTreeError::WrongLeafIndex)
     }
+
+    /// Get node value, which is zero for the nodes without leaves below
+    fn node(&self, layer: usize, index: usize) -> Hash::Output {
+        self.layers[layer]
+            .get(index)
+            .copied()
+            .unwrap_or(Hash::ZEROS[layer])
+    }
 }
 
 /// Creation merkle tree from array
@@ -44,35 +140,25 @@ impl<const DEPTH: usize, Hash: MerkleTreeHasher> TryFrom<&[Hash::Output]>
     type Error = MerkleTreeError;
 
     fn try_from(source: &[Hash::Output]) -> Result<MerkleTree<DEPTH, Hash>, MerkleTreeError> {
-        if source.len() >= 1 << DEPTH {
+        if source.len() > 1 << DEPTH {
             return Err(MerkleTreeError::VecTooLong);
         }
 
-        let mut leaves = vec![Hash::ZEROS[0]; 1 << DEPTH];
-
-        // Fill leaves using source vec
-        for (i, elem) in source.iter().enumerate() {
-            leaves[i] = *elem;
-        }
-        let mut layers = Vec::with_capacity(DEPTH);
-        layers.push(leaves);
+        let mut tree = Self::new()?;
 
-        // Compute all node hashes
-        for i in 1..DEPTH {
-            layers.push(vec![Hash::ZEROS[i]; 1 << (DEPTH - i)]);
-
-            for j in 0..source.len() / (1 << i) {
-                layers[i][j] =
-                    Hash::hash_left_right(layers[i - 1][2 * j], layers[i - 1][2 * j + 1]);
-            }
+        for elem in source {
+            tree.append(*elem)?;
         }
 
-        Ok(Self { layers })
+        Ok(tree)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum MerkleTreeError {
     VecTooLong,
     WrongLeafIndex,
+    MerkleTreeIsFull,
+    DepthTooLong,
+    DepthIsZero,
 }
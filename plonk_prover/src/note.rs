@@ -0,0 +1,312 @@
+#![cfg(feature = "proof_generator")]
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use dusk_bls12_381::BlsScalar;
+use dusk_poseidon::sponge;
+use shared::functions::{bytes_to_scalar, scalar_to_bytes, u128_to_scalar};
+use shared::public_types::*;
+
+use crate::commitment_generation::{generate_commitment, GeneratedCommitment};
+
+/// Prefix of every note string
+const NOTE_PREFIX: &str = "slushie";
+
+/// Current version of the note format
+pub const NOTE_VERSION: u8 = 1;
+
+/// Checksum size in bytes
+const CHECKSUM_SIZE: usize = 4;
+
+/// Payload size in bytes: version, nullifier, randomness and checksum
+const PAYLOAD_SIZE: usize = 1 + 32 + 32 + CHECKSUM_SIZE;
+
+/// Note which keeps all secrets needed to withdraw a deposit
+///
+/// String format is `slushie-<denomination>-<network>-0x<version || k || r || checksum>`,
+/// where checksum is the first 4 bytes of Poseidon hash of all other note fields
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    /// Deposit size
+    pub denomination: u128,
+    /// Network name
+    pub network: String,
+    /// Nullifier
+    pub nullifier: Nullifier,
+    /// Randomness
+    pub randomness: Randomness,
+}
+
+impl Note {
+    /// Generate note with random nullifier and randomness
+    pub fn generate(denomination: u128, network: &str) -> Result<Self, NoteError> {
+        check_network(network)?;
+
+        let GeneratedCommitment {
+            nullifier,
+            randomness,
+            ..
+        } = generate_commitment();
+
+        Ok(Self {
+            denomination,
+            network: network.to_string(),
+            nullifier,
+            randomness,
+        })
+    }
+
+    /// Parse note from string
+    pub fn parse(note: &str) -> Result<Self, NoteError> {
+        let parts: Vec<&str> = note.split('-').collect();
+
+        if parts.len() != 4 {
+            return Err(NoteError::WrongFormat);
+        }
+
+        if parts[0] != NOTE_PREFIX {
+            return Err(NoteError::WrongPrefix);
+        }
+
+        let denomination = parts[1]
+            .parse::<u128>()
+            .map_err(|_| NoteError::WrongDenomination)?;
+
+        let network = parts[2];
+        check_network(network)?;
+
+        let payload = hex::decode(parts[3].strip_prefix("0x").ok_or(NoteError::WrongFormat)?)
+            .map_err(|_| NoteError::WrongHex)?;
+
+        if payload.len() != PAYLOAD_SIZE {
+            return Err(NoteError::WrongLength);
+        }
+
+        if payload[0] != NOTE_VERSION {
+            return Err(NoteError::UnsupportedVersion(payload[0]));
+        }
+
+        let note = Self {
+            denomination,
+            network: network.to_string(),
+            nullifier: payload[1..33].try_into().unwrap(),
+            randomness: payload[33..65].try_into().unwrap(),
+        };
+
+        if payload[65..] != note.checksum() {
+            return Err(NoteError::WrongChecksum);
+        }
+
+        Ok(note)
+    }
+
+    /// Compute commitment `C = H(k || r)`
+    pub fn commitment(&self) -> PoseidonHash {
+        scalar_to_bytes(sponge::hash(&[
+            bytes_to_scalar(self.nullifier),
+            bytes_to_scalar(self.randomness),
+        ]))
+    }
+
+    /// Compute nullifier hash `h = H(k)`
+    pub fn nullifier_hash(&self) -> PoseidonHash {
+        scalar_to_bytes(sponge::hash(&[bytes_to_scalar(self.nullifier)]))
+    }
+
+    /// Compute checksum of the note fields
+    fn checksum(&self) -> [u8; CHECKSUM_SIZE] {
+        let mut inputs = vec![
+            BlsScalar::from(NOTE_VERSION as u64),
+            u128_to_scalar(self.denomination),
+        ];
+
+        // Network name is packed in 8-byte chunks
+        inputs.extend(self.network.as_bytes().chunks(8).map(|chunk| {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            BlsScalar::from(u64::from_be_bytes(word))
+        }));
+
+        inputs.push(bytes_to_scalar(self.nullifier));
+        inputs.push(bytes_to_scalar(self.randomness));
+
+        let hash = scalar_to_bytes(sponge::hash(&inputs));
+
+        hash[..CHECKSUM_SIZE].try_into().unwrap()
+    }
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut payload = Vec::with_capacity(PAYLOAD_SIZE);
+        payload.push(NOTE_VERSION);
+        payload.extend_from_slice(&self.nullifier);
+        payload.extend_from_slice(&self.randomness);
+        payload.extend_from_slice(&self.checksum());
+
+        write!(
+            f,
+            "{}-{}-{}-0x{}",
+            NOTE_PREFIX,
+            self.denomination,
+            self.network,
+            hex::encode(payload)
+        )
+    }
+}
+
+impl FromStr for Note {
+    type Err = NoteError;
+
+    fn from_str(note: &str) -> Result<Self, Self::Err> {
+        Self::parse(note)
+    }
+}
+
+/// Network name should be non-empty and contain only ASCII letters and digits
+fn check_network(network: &str) -> Result<(), NoteError> {
+    if network.is_empty() || !network.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(NoteError::WrongNetwork);
+    }
+
+    Ok(())
+}
+
+/// Errors which may be returned while generating or parsing a note
+#[derive(Debug, PartialEq, Eq)]
+pub enum NoteError {
+    /// Note has not 4 parts separated by `-`
+    WrongFormat,
+    /// Note does not start with `slushie`
+    WrongPrefix,
+    /// Denomination is not an unsigned integer
+    WrongDenomination,
+    /// Network name is empty or contains not alphanumeric characters
+    WrongNetwork,
+    /// Payload is not in hex format
+    WrongHex,
+    /// Payload has wrong length
+    WrongLength,
+    /// Note version is not supported
+    UnsupportedVersion(u8),
+    /// Checksum does not match note fields
+    WrongChecksum,
+}
+
+impl fmt::Display for NoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoteError::WrongFormat => write!(f, "Note should have 4 parts separated by '-'"),
+            NoteError::WrongPrefix => write!(f, "Note should start with '{}'", NOTE_PREFIX),
+            NoteError::WrongDenomination => write!(f, "Note denomination is not a number"),
+            NoteError::WrongNetwork => write!(f, "Note network should be alphanumeric"),
+            NoteError::WrongHex => write!(f, "Note secrets are not in hex format"),
+            NoteError::WrongLength => write!(f, "Note secrets have wrong length"),
+            NoteError::UnsupportedVersion(version) => {
+                write!(f, "Note version {} is not supported", version)
+            }
+            NoteError::WrongChecksum => write!(f, "Note checksum is wrong"),
+        }
+    }
+}
+
+impl std::error::Error for NoteError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn note_string_roundtrip() {
+        let note = Note::generate(1_000_000_000_000, "rococo").unwrap();
+
+        let parsed = Note::parse(&note.to_string()).unwrap();
+
+        assert_eq!(parsed, note);
+        assert_eq!(parsed.commitment(), note.commitment());
+        assert_eq!(parsed.nullifier_hash(), note.nullifier_hash());
+    }
+
+    #[test]
+    fn note_matches_generated_commitment() {
+        let GeneratedCommitment {
+            nullifier,
+            randomness,
+            commitment_bytes,
+            nullifier_hash_bytes,
+        } = generate_commitment();
+
+        let note = Note {
+            denomination: 100,
+            network: "local".to_string(),
+            nullifier,
+            randomness,
+        };
+
+        assert_eq!(note.commitment(), commitment_bytes);
+        assert_eq!(note.nullifier_hash(), nullifier_hash_bytes);
+    }
+
+    #[test]
+    fn corrupted_note_is_rejected() {
+        let note = Note::generate(100, "local").unwrap().to_string();
+
+        // Flip one hex digit of the secrets
+        let mut corrupted = note.clone().into_bytes();
+        let position = note.len() - 20;
+        corrupted[position] = if corrupted[position] == b'0' {
+            b'1'
+        } else {
+            b'0'
+        };
+        let corrupted = String::from_utf8(corrupted).unwrap();
+        assert_eq!(Note::parse(&corrupted), Err(NoteError::WrongChecksum));
+
+        // Change denomination
+        let changed = note.replacen("-100-", "-101-", 1);
+        assert_eq!(Note::parse(&changed), Err(NoteError::WrongChecksum));
+
+        // Change network
+        let changed = note.replacen("-local-", "-rococo-", 1);
+        assert_eq!(Note::parse(&changed), Err(NoteError::WrongChecksum));
+    }
+
+    #[test]
+    fn malformed_note_is_rejected() {
+        let note = Note::generate(100, "local").unwrap().to_string();
+
+        assert_eq!(
+            Note::parse("slushie-100-local"),
+            Err(NoteError::WrongFormat)
+        );
+        assert_eq!(
+            Note::parse(&note.replacen("slushie", "tornado", 1)),
+            Err(NoteError::WrongPrefix)
+        );
+        assert_eq!(
+            Note::parse(&note.replacen("-100-", "-abc-", 1)),
+            Err(NoteError::WrongDenomination)
+        );
+        assert_eq!(
+            Note::parse(&note.replacen("-0x", "-", 1)),
+            Err(NoteError::WrongFormat)
+        );
+        assert_eq!(
+            Note::parse(&note.replacen("-0x", "-0xzz", 1)),
+            Err(NoteError::WrongHex)
+        );
+        assert_eq!(
+            Note::parse(&note[..note.len() - 2]),
+            Err(NoteError::WrongLength)
+        );
+        assert_eq!(
+            Note::parse(&note.replacen("-0x01", "-0x02", 1)),
+            Err(NoteError::UnsupportedVersion(2))
+        );
+        assert_eq!(Note::generate(100, "my-net"), Err(NoteError::WrongNetwork));
+        assert_eq!(Note::generate(100, ""), Err(NoteError::WrongNetwork));
+    }
+}
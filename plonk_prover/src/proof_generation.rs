@@ -1,9 +1,11 @@
 #![cfg(feature = "proof_generator")]
 use crate::circuit::*;
+use crate::error::SlushieProverError;
+use crate::hasher::{MerkleTreeHasher, Poseidon};
 use crate::utils::*;
 use dusk_bytes::Serializable;
 use shared::constants::DEFAULT_DEPTH;
-use shared::functions::bytes_to_u64;
+use shared::functions::{account_to_scalars, bytes_to_scalar, is_canonical, u128_to_scalar};
 use shared::public_types::*;
 
 use dusk_plonk::prelude::*;
@@ -11,6 +13,164 @@ use dusk_poseidon::sponge;
 
 use rand_core::OsRng;
 
+///Private and public inputs which are needed to generate a single proof
+#[allow(non_snake_case)]
+pub struct WithdrawalWitness<const DEPTH: usize> {
+    ///Leaf index
+    pub l: usize,
+    ///Root
+    pub R: PoseidonHash,
+    ///Tree opening
+    pub o: [PoseidonHash; DEPTH],
+    ///Nullifier
+    pub k: Nullifier,
+    ///Randomness
+    pub r: Randomness,
+    ///Recipient address
+    pub A: Pubkey,
+    ///Relayer address
+    pub t: Pubkey,
+    ///Fee
+    pub f: u64,
+    ///Denomination of the pool
+    pub N: u128,
+    ///Domain of the contract and the chain, see `withdrawal_domain`
+    pub D: PoseidonHash,
+}
+
+impl<const DEPTH: usize> WithdrawalWitness<DEPTH> {
+    ///Check the witness off-circuit before any proving work,
+    ///so an inconsistent input is reported instead of an invalid proof
+    pub fn check_witness(&self) -> Result<(), SlushieProverError> {
+        let path = index_to_path::<DEPTH>(self.l).map_err(|_| SlushieProverError::InvalidIndex)?;
+
+        //Field elements should be encoded canonically
+        if !is_canonical(self.R) || !is_canonical(self.D) {
+            return Err(SlushieProverError::InvalidPublicInputs);
+        }
+        if !is_canonical(self.k) || !is_canonical(self.r) {
+            return Err(SlushieProverError::InvalidSecret);
+        }
+        if !self.o.iter().all(|node| is_canonical(*node)) {
+            return Err(SlushieProverError::InvalidOpening);
+        }
+
+        if self.f as u128 > self.N {
+            return Err(SlushieProverError::FeeExceedsDenomination);
+        }
+
+        //Opening should fit the leaf index in the append-only tree:
+        //a left sister is never empty and a right sister is empty if any lower right sister is
+        let mut right_sister_empty = false;
+        for (i, (bit, sister)) in path.iter().zip(self.o.iter()).enumerate() {
+            let empty = Poseidon::ZEROS.get(i) == Some(sister);
+            match (*bit, empty) {
+                (1, true) => return Err(SlushieProverError::PathMismatch),
+                (0, true) => right_sister_empty = true,
+                (0, false) if right_sister_empty => return Err(SlushieProverError::PathMismatch),
+                _ => {}
+            }
+        }
+
+        //Recompute commitment and root from the opening and the path
+        let commitment = Poseidon::scalar_to_bytes(sponge::hash(&[
+            bytes_to_scalar(self.k),
+            bytes_to_scalar(self.r),
+        ]));
+        let root = path
+            .iter()
+            .zip(self.o.iter())
+            .fold(commitment, |current, (bit, sister)| {
+                if *bit == 1 {
+                    Poseidon::hash_left_right(*sister, current)
+                } else {
+                    Poseidon::hash_left_right(current, *sister)
+                }
+            });
+
+        if root != self.R {
+            return Err(SlushieProverError::RootMismatch);
+        }
+
+        Ok(())
+    }
+
+    ///Create circuit filled with the witness values
+    fn to_circuit(&self) -> Result<SlushieCircuit<DEPTH>, SlushieProverError> {
+        Ok(SlushieCircuit::<DEPTH> {
+            R: bytes_to_scalar(self.R),
+            r: bytes_to_scalar(self.r),
+            k: bytes_to_scalar(self.k),
+            h: sponge::hash(&[bytes_to_scalar(self.k)]),
+            A: account_to_scalars(self.A),
+            t: account_to_scalars(self.t),
+            f: self.f.into(),
+            N: u128_to_scalar(self.N),
+            D: bytes_to_scalar(self.D),
+            o: Array(self.o),
+            p: Array(index_to_path(self.l).map_err(|_| SlushieProverError::InvalidIndex)?),
+        })
+    }
+}
+
+///Prover which compiles the circuit only once and then can generate many proofs
+pub struct SlushieProver<const DEPTH: usize> {
+    ///Prover key of the compiled circuit
+    pk: ProverKey,
+    ///Commitment key
+    ck: CommitKey,
+}
+
+impl<const DEPTH: usize> SlushieProver<DEPTH> {
+    ///Create prover by compiling circuit with public parameters
+    ///Depth can be custom
+    pub fn new(pp: &[u8]) -> Result<Self, SlushieProverError> {
+        //Read public parameters
+        let pp =
+            PublicParameters::from_slice(pp).map_err(|_| SlushieProverError::KeyDeserialization)?;
+
+        //Compile circuit
+        let mut circuit = SlushieCircuit::<DEPTH>::default();
+        let (pk, _vd) = circuit.compile(&pp)?;
+
+        //Trim commitment key to the circuit size
+        let (ck, _) = pp.trim(circuit.padded_gates())?;
+
+        Ok(Self { pk, ck })
+    }
+
+    ///Create prover from precompiled prover data and commitment key
+    pub fn from_prover_data(pd: &[u8], ck: &[u8]) -> Result<Self, SlushieProverError> {
+        //Read prover data and commit key
+        let pk = ProverKey::from_slice(pd).map_err(|_| SlushieProverError::KeyDeserialization)?;
+        let ck = CommitKey::from_slice(ck).map_err(|_| SlushieProverError::KeyDeserialization)?;
+
+        Ok(Self { pk, ck })
+    }
+
+    ///Generate serialized proof for provided witness
+    pub fn prove(
+        &self,
+        witness: &WithdrawalWitness<DEPTH>,
+    ) -> Result<SerializedProof, SlushieProverError> {
+        //Check witness before proving
+        witness.check_witness()?;
+
+        //Create circuit
+        let mut circuit = witness.to_circuit()?;
+
+        // New Prover instance
+        let mut prover = Prover::new(TRANSCRIPT_INIT);
+
+        // Fill witnesses for Prover
+        circuit.gadget(prover.composer_mut())?;
+
+        // Add prover data to Prover
+        prover.prover_key = Some(self.pk.clone());
+        Ok(prover.prove(&self.ck, &mut OsRng)?.to_bytes())
+    }
+}
+
 ///Generate serialized proof in cases when public parameters is available
 ///Depth can be custom
 #[allow(clippy::too_many_arguments)]
@@ -25,40 +185,32 @@ pub fn prove<const DEPTH: usize>(
     //Tree opening
     o: [PoseidonHash; DEPTH],
     //Nullifier
-    k: u32,
+    k: Nullifier,
     //Randomness
-    r: u32,
+    r: Randomness,
     //Recipient address
     A: Pubkey,
     //Relayer address
     t: Pubkey,
     //Fee
     f: u64,
-) -> Result<SerializedProof, Error> {
-    //Read public parameters
-    let pp = PublicParameters::from_slice(pp)?;
-
-    //Compile circuit
-    let mut circuit = SlushieCircuit::<DEPTH>::default();
-    let (pk, _vd) = circuit.compile(&pp)?;
-
-    //Create circuit
-    let mut circuit = SlushieCircuit::<DEPTH> {
-        R: BlsScalar(bytes_to_u64(R)),
-        r: (r as u64).into(),
-        k: (k as u64).into(),
-        h: sponge::hash(&[(k as u64).into()]),
-        A: BlsScalar::from_raw(bytes_to_u64(A)),
-        t: BlsScalar::from_raw(bytes_to_u64(t)),
-        f: f.into(),
-        o: Array(o),
-        p: Array(index_to_path(l).map_err(|_| Error::ProofVerificationError)?),
-    };
-
-    //Generate proof
-    circuit
-        .prove(&pp, &pk, TRANSCRIPT_INIT, &mut OsRng)
-        .map(|proof| proof.to_bytes())
+    //Denomination
+    N: u128,
+    //Domain
+    D: PoseidonHash,
+) -> Result<SerializedProof, SlushieProverError> {
+    SlushieProver::<DEPTH>::new(pp)?.prove(&WithdrawalWitness {
+        l,
+        R,
+        o,
+        k,
+        r,
+        A,
+        t,
+        f,
+        N,
+        D,
+    })
 }
 
 ///Generate serialized proof in cases when compilation with public parameters is too long
@@ -78,40 +230,30 @@ pub fn prove_with_vd(
     //Tree opening
     o: [PoseidonHash; DEFAULT_DEPTH],
     //Nullifier
-    k: u32,
+    k: Nullifier,
     //Randomness
-    r: u32,
+    r: Randomness,
     //Recipient address
     A: Pubkey,
     //Relayer address
     t: Pubkey,
     //Fee
     f: u64,
-) -> Result<SerializedProof, Error> {
-    //Read prover data and commit key
-    let pd = ProverKey::from_slice(pd)?;
-    let ck = CommitKey::from_slice(ck)?;
-
-    // New Prover instance
-    let mut prover = Prover::new(TRANSCRIPT_INIT);
-
-    //Create circuit
-    let mut circuit = SlushieCircuit::<DEFAULT_DEPTH> {
-        R: BlsScalar(bytes_to_u64(R)),
-        r: (r as u64).into(),
-        k: (k as u64).into(),
-        h: sponge::hash(&[(k as u64).into()]),
-        A: BlsScalar::from_raw(bytes_to_u64(A)),
-        t: BlsScalar::from_raw(bytes_to_u64(t)),
-        f: f.into(),
-        o: Array(o),
-        p: Array(index_to_path(l).map_err(|_| Error::ProofVerificationError)?),
-    };
-
-    // Fill witnesses for Prover
-    circuit.gadget(prover.composer_mut())?;
-
-    // Add prover data to Prover
-    prover.prover_key = Some(pd);
-    prover.prove(&ck, &mut OsRng).map(|proof| proof.to_bytes())
+    //Denomination
+    N: u128,
+    //Domain
+    D: PoseidonHash,
+) -> Result<SerializedProof, SlushieProverError> {
+    SlushieProver::<DEFAULT_DEPTH>::from_prover_data(pd, ck)?.prove(&WithdrawalWitness {
+        l,
+        R,
+        o,
+        k,
+        r,
+        A,
+        t,
+        f,
+        N,
+        D,
+    })
 }
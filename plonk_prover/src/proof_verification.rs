@@ -3,13 +3,18 @@ use alloc::vec::Vec;
 use dusk_bls12_381::BlsScalar;
 use dusk_bytes::Serializable;
 use dusk_plonk::prelude::*;
-use shared::functions::bytes_to_u64;
+use dusk_poseidon::sponge;
+use shared::functions::{
+    account_to_scalars, bytes_to_u64, is_canonical, scalar_to_bytes, u128_to_scalar,
+};
 
 use crate::circuit::*;
+use crate::error::SlushieProverError;
 use shared::public_types::*;
 
 ///Verify serialized proof in cases when public parameters is available
 ///Depth can be custom
+#[allow(clippy::too_many_arguments)]
 #[allow(non_snake_case)]
 pub fn verify<const DEPTH: usize>(
     //Public parameters
@@ -24,30 +29,48 @@ pub fn verify<const DEPTH: usize>(
     t: Pubkey,
     //Fee
     f: u64,
+    //Denomination
+    N: u128,
+    //Domain
+    D: PoseidonHash,
     //Proof
     proof: &SerializedProof,
-) -> Result<(), Error> {
+) -> Result<(), SlushieProverError> {
+    //Field elements should be encoded canonically
+    if ![h, R, D].iter().all(|bytes| is_canonical(*bytes)) {
+        return Err(SlushieProverError::InvalidPublicInputs);
+    }
+
     //Read public parameters
-    let pp = PublicParameters::from_slice(pp)?;
+    let pp =
+        PublicParameters::from_slice(pp).map_err(|_| SlushieProverError::KeyDeserialization)?;
 
     //Compile circuit
     let mut circuit = SlushieCircuit::<DEPTH>::default();
     let (_pk, vd) = circuit.compile(&pp)?;
 
     // Proof deserialization
-    let proof = Proof::from_bytes(proof)?;
+    let proof = Proof::from_bytes(proof).map_err(Error::from)?;
 
     // Create public inputs
+    let [A_high, A_low] = account_to_scalars(A);
+    let [t_high, t_low] = account_to_scalars(t);
     let public_inputs: Vec<PublicInputValue> = vec![
         BlsScalar(bytes_to_u64(R)).into(),
         BlsScalar(bytes_to_u64(h)).into(),
-        BlsScalar::from_raw(bytes_to_u64(A)).into(),
-        BlsScalar::from_raw(bytes_to_u64(t)).into(),
+        A_high.into(),
+        A_low.into(),
+        t_high.into(),
+        t_low.into(),
         BlsScalar::from(f).into(),
+        u128_to_scalar(N).into(),
+        BlsScalar(bytes_to_u64(D)).into(),
     ];
 
     // Verify proof using public inputs
-    SlushieCircuit::<DEPTH>::verify(&pp, &vd, &proof, &public_inputs, TRANSCRIPT_INIT)
+    SlushieCircuit::<DEPTH>::verify(&pp, &vd, &proof, &public_inputs, TRANSCRIPT_INIT)?;
+
+    Ok(())
 }
 
 ///Verify serialized proof in cases when public parameters is too large
@@ -69,33 +92,186 @@ pub fn verify_with_vd(
     t: Pubkey,
     //Fee
     f: u64,
+    //Denomination
+    N: u128,
+    //Domain
+    D: PoseidonHash,
     //Proof
     proof: &SerializedProof,
-) -> Result<(), Error> {
-    // Verifier data deserialization
-    let vd = VerifierData::from_slice(vd)?;
+) -> Result<(), SlushieProverError> {
+    SlushieVerifier::new(vd, opening_key)?.verify(
+        &ProofPublicInputs {
+            nullifier_hash: h,
+            root: R,
+            recipient: A,
+            relayer: t,
+            fee: f,
+            denomination: N,
+            domain: D,
+        },
+        proof,
+    )
+}
 
-    //Opening key deserialization
-    let opening_key = OpeningKey::from_bytes(opening_key)?;
+///Domain of the withdrawal proof, which binds the proof to the contract on the chain,
+///so it can't be replayed against another deployment with the same root
+///It is Poseidon hash of the contract address and the chain identifier
+pub fn withdrawal_domain(contract: Pubkey, chain_id: ChainId) -> PoseidonHash {
+    let [contract_high, contract_low] = account_to_scalars(contract);
+    let [chain_id_high, chain_id_low] = account_to_scalars(chain_id);
 
-    // Proof deserialization
-    let proof = Proof::from_bytes(proof)?;
-
-    // Setup for verifier
-    let mut verifier = Verifier::new(TRANSCRIPT_INIT);
-    verifier.verifier_key.replace(*vd.key());
-
-    let pi_indexes = vd.public_inputs_indexes();
-    let public_inputs = [
-        BlsScalar::zero(),
-        BlsScalar::zero(),
-        BlsScalar::zero(),
-        -BlsScalar(bytes_to_u64(R)),
-        -BlsScalar(bytes_to_u64(h)),
-        -BlsScalar::from_raw(bytes_to_u64(A)),
-        -BlsScalar::from_raw(bytes_to_u64(t)),
-        -BlsScalar::from(f),
-    ];
+    scalar_to_bytes(sponge::hash(&[
+        contract_high,
+        contract_low,
+        chain_id_high,
+        chain_id_low,
+    ]))
+}
+
+///Public inputs of a single withdrawal proof
+pub struct ProofPublicInputs {
+    ///Nullifier hash
+    pub nullifier_hash: PoseidonHash,
+    ///Root
+    pub root: PoseidonHash,
+    ///Recipient address
+    pub recipient: Pubkey,
+    ///Relayer address
+    pub relayer: Pubkey,
+    ///Fee
+    pub fee: u64,
+    ///Denomination of the pool
+    pub denomination: u128,
+    ///Domain of the contract and the chain, see `withdrawal_domain`
+    pub domain: PoseidonHash,
+}
+
+impl ProofPublicInputs {
+    ///Check that field elements are encoded canonically
+    fn is_canonical(&self) -> bool {
+        [self.root, self.nullifier_hash, self.domain]
+            .iter()
+            .all(|bytes| is_canonical(*bytes))
+    }
+
+    ///Public inputs in the form which is expected by verifier:
+    ///every value is negated and placed at the index of its gate in the circuit
+    fn to_scalars(&self, pi_indexes: &[usize]) -> Vec<BlsScalar> {
+        let [recipient_high, recipient_low] = account_to_scalars(self.recipient);
+        let [relayer_high, relayer_low] = account_to_scalars(self.relayer);
+
+        //Values in the order they are appended to the circuit
+        let values = [
+            BlsScalar(bytes_to_u64(self.root)),
+            BlsScalar(bytes_to_u64(self.nullifier_hash)),
+            recipient_high,
+            recipient_low,
+            relayer_high,
+            relayer_low,
+            BlsScalar::from(self.fee),
+            u128_to_scalar(self.denomination),
+            BlsScalar(bytes_to_u64(self.domain)),
+        ];
+
+        let mut public_inputs =
+            vec![BlsScalar::zero(); pi_indexes.iter().max().map_or(0, |index| index + 1)];
+        for (value, index) in values.iter().zip(pi_indexes) {
+            public_inputs[*index] = -value;
+        }
+
+        public_inputs
+    }
+}
+
+///Verifier which holds deserialized verifier data and opening key,
+///so they are not deserialized again for every proof
+pub struct SlushieVerifier {
+    ///Verifier data of the compiled circuit
+    vd: VerifierData,
+    ///Opening key
+    opening_key: OpeningKey,
+}
+
+impl SlushieVerifier {
+    ///Create verifier from serialized verifier data and opening key
+    pub fn new(
+        vd: &[u8],
+        opening_key: &[u8; OpeningKey::SIZE],
+    ) -> Result<Self, SlushieProverError> {
+        // Verifier data deserialization
+        let vd =
+            VerifierData::from_slice(vd).map_err(|_| SlushieProverError::KeyDeserialization)?;
+
+        //Opening key deserialization
+        let opening_key = OpeningKey::from_bytes(opening_key)
+            .map_err(|_| SlushieProverError::KeyDeserialization)?;
+
+        Ok(Self { vd, opening_key })
+    }
+
+    ///Depth of the merkle tree which the verifier data is compiled for,
+    ///`None` if the verifier data doesn't have the public inputs of the circuit
+    pub fn depth(&self) -> Option<usize> {
+        circuit_depth(self.vd.public_inputs_indexes())
+    }
+
+    ///Create verifier by compiling circuit with public parameters
+    ///Depth can be custom
+    pub fn from_public_parameters<const DEPTH: usize>(
+        pp: &[u8],
+    ) -> Result<Self, SlushieProverError> {
+        //Read public parameters
+        let pp =
+            PublicParameters::from_slice(pp).map_err(|_| SlushieProverError::KeyDeserialization)?;
+
+        //Compile circuit
+        let mut circuit = SlushieCircuit::<DEPTH>::default();
+        let (_pk, vd) = circuit.compile(&pp)?;
+
+        //Trim opening key to the circuit size
+        let (_, opening_key) = pp.trim(circuit.padded_gates())?;
+
+        Ok(Self { vd, opening_key })
+    }
+
+    ///Verify serialized proof with its public inputs
+    pub fn verify(
+        &self,
+        public_inputs: &ProofPublicInputs,
+        proof: &SerializedProof,
+    ) -> Result<(), SlushieProverError> {
+        //Field elements should be encoded canonically
+        if !public_inputs.is_canonical() {
+            return Err(SlushieProverError::InvalidPublicInputs);
+        }
+
+        // Proof deserialization
+        let proof = Proof::from_bytes(proof).map_err(Error::from)?;
+
+        // Setup for verifier
+        let mut verifier = Verifier::new(TRANSCRIPT_INIT);
+        verifier.verifier_key.replace(*self.vd.key());
+
+        let pi_indexes = self.vd.public_inputs_indexes();
+        verifier.verify(
+            &proof,
+            &self.opening_key,
+            &public_inputs.to_scalars(pi_indexes),
+            pi_indexes,
+        )?;
+
+        Ok(())
+    }
 
-    verifier.verify(&proof, &opening_key, &public_inputs, pi_indexes)
+    ///Verify batch of serialized proofs with their public inputs
+    ///Returns verification result for every proof in the same order
+    pub fn verify_batch(
+        &self,
+        batch: &[(ProofPublicInputs, SerializedProof)],
+    ) -> Vec<Result<(), SlushieProverError>> {
+        batch
+            .iter()
+            .map(|(public_inputs, proof)| self.verify(public_inputs, proof))
+            .collect()
+    }
 }
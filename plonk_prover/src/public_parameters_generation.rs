@@ -1,28 +1,40 @@
 #![cfg(feature = "proof_generator")]
 
 use crate::circuit::{SlushieCircuit, CIRCUIT_SIZE};
+use crate::error::SlushieProverError;
 use alloc::vec::Vec;
 use dusk_bytes::Serializable;
 use dusk_plonk::prelude::*;
 use rand_core::OsRng;
 use shared::constants::DEFAULT_DEPTH;
 
-pub fn generate_test_public_parameters() -> Result<Vec<u8>, Error> {
-    PublicParameters::setup(CIRCUIT_SIZE, &mut OsRng).map(|pp| pp.to_var_bytes())
+pub fn generate_test_public_parameters() -> Result<Vec<u8>, SlushieProverError> {
+    Ok(PublicParameters::setup(CIRCUIT_SIZE, &mut OsRng)?.to_var_bytes())
 }
 
-pub fn generate_verifier_data(pp: &[u8]) -> Result<(Vec<u8>, [u8; OpeningKey::SIZE]), Error> {
-    let pp = PublicParameters::from_slice(pp)?;
+pub fn generate_verifier_data(
+    pp: &[u8],
+) -> Result<(Vec<u8>, [u8; OpeningKey::SIZE]), SlushieProverError> {
+    generate_verifier_data_with_depth::<DEFAULT_DEPTH>(pp)
+}
 
-    let mut circuit = SlushieCircuit::<DEFAULT_DEPTH>::default();
+///Generate verifier data for the circuit with custom depth
+pub fn generate_verifier_data_with_depth<const DEPTH: usize>(
+    pp: &[u8],
+) -> Result<(Vec<u8>, [u8; OpeningKey::SIZE]), SlushieProverError> {
+    let pp =
+        PublicParameters::from_slice(pp).map_err(|_| SlushieProverError::KeyDeserialization)?;
+
+    let mut circuit = SlushieCircuit::<DEPTH>::default();
 
     let (_, vd) = circuit.compile(&pp)?;
 
     Ok((vd.to_var_bytes(), pp.opening_key().to_bytes()))
 }
 
-pub fn generate_prover_data(pp: &[u8]) -> Result<(Vec<u8>, Vec<u8>), Error> {
-    let pp = PublicParameters::from_slice(pp)?;
+pub fn generate_prover_data(pp: &[u8]) -> Result<(Vec<u8>, Vec<u8>), SlushieProverError> {
+    let pp =
+        PublicParameters::from_slice(pp).map_err(|_| SlushieProverError::KeyDeserialization)?;
 
     let mut circuit = SlushieCircuit::<DEFAULT_DEPTH>::default();
 
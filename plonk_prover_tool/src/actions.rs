@@ -1,31 +1,59 @@
 use hex::ToHex;
+use plonk_prover::hasher::Poseidon;
+use plonk_prover::merkle_tree::MerkleTree;
+use plonk_prover::note::Note;
 use plonk_prover::public_parameters_generation::*;
-use plonk_prover::{prove, GeneratedCommitment};
+use plonk_prover::{prove, verify, verify_with_vd, GeneratedCommitment, SlushieProverError};
+use shared::constants::DEFAULT_DEPTH;
 use sp_core::crypto::{AccountId32, Ss58Codec};
+use std::error::Error;
 
-use crate::utils::{read_pp, write_to_file};
+use crate::utils::{
+    parse_commitment, parse_commitments, parse_domain, parse_secret, read_from_file, read_pp,
+    write_to_file,
+};
 use crate::{commands::Commands, utils::parse_tree_openings};
 
 /// Generate proof and write it to file
-pub(crate) fn generate_proof(args: &Commands) {
+pub(crate) fn generate_proof(args: &Commands) -> Result<(), Box<dyn Error>> {
     // Get arguments from command
-    let (pp, l, root, o, k, r, a, t, f, output_file) = if let Commands::GenerateProof {
-        pp,
-        l,
-        root,
-        o,
-        k,
-        r,
-        a,
-        t,
-        f,
-        output_file,
-    } = args
-    {
-        (pp, l, root, o, k, r, a, t, f, output_file)
-    } else {
-        panic!("Wrong Command!")
-    };
+    let (pp, l, root, o, k, r, note, a, t, f, n, contract, chain_id, output_file) =
+        if let Commands::GenerateProof {
+            pp,
+            l,
+            root,
+            o,
+            k,
+            r,
+            note,
+            a,
+            t,
+            f,
+            n,
+            contract,
+            chain_id,
+            output_file,
+        } = args
+        {
+            (
+                pp,
+                l,
+                root,
+                o,
+                k,
+                r,
+                note,
+                a,
+                t,
+                f,
+                n,
+                contract,
+                chain_id,
+                output_file,
+            )
+        } else {
+            panic!("Wrong Command!")
+        };
 
     // Read serialized pp
     let pp_bytes = read_pp(pp);
@@ -46,14 +74,167 @@ pub(crate) fn generate_proof(args: &Commands) {
     // Read and parse root
     let root: [u8; 32] = hex::decode(root).unwrap().try_into().unwrap();
 
+    // Read and parse nullifier, randomness and denomination, either from the note or separately
+    let (k, r, n) = match (note, k, r, n) {
+        (Some(note), _, _, n) => {
+            let note = Note::parse(note)?;
+            if matches!(n, Some(n) if *n != note.denomination) {
+                panic!("Denomination differs from the note denomination");
+            }
+
+            (note.nullifier, note.randomness, note.denomination)
+        }
+        (None, Some(k), Some(r), Some(n)) => (parse_secret(k), parse_secret(r), *n),
+        _ => panic!("Either note or nullifier, randomness and denomination should be provided"),
+    };
+
+    // Compute domain of the contract and the chain
+    let d = parse_domain(contract, chain_id);
+
     // Generate proof
-    let proof = prove(&pp_bytes, *l, root, o, *k, *r, a, t, *f).expect("Error generating proof");
+    let proof = prove(&pp_bytes, *l, root, o, k, r, a, t, *f, n, d)?;
 
     // Write serialized proof to file
     write_to_file(output_file, &proof);
 
     println!("Success! Your proof generated in {}!", output_file);
     println!("You can use Proof to call withdraw contract method");
+
+    Ok(())
+}
+
+/// Generate root, leaf index and tree opening for the commitment
+pub(crate) fn tree_opening(args: &Commands) {
+    // Get arguments from command
+    let (commitments, commitment, output_file) = if let Commands::TreeOpening {
+        commitments,
+        commitment,
+        output_file,
+    } = args
+    {
+        (commitments, commitment, output_file)
+    } else {
+        panic!("Wrong Command!")
+    };
+
+    // Read commitments and the commitment to open
+    let commitments = parse_commitments(commitments);
+    let commitment = parse_commitment(commitment);
+
+    // Find leaf index of the commitment
+    let l = commitments
+        .iter()
+        .position(|c| *c == commitment)
+        .expect("Commitment is not found among commitments");
+
+    // Build tree from commitments in deposit order
+    let tree: MerkleTree<DEFAULT_DEPTH, Poseidon> = commitments
+        .as_slice()
+        .try_into()
+        .expect("Could not build Merkle tree");
+
+    let opening = tree.opening(l).expect("Could not generate tree opening");
+
+    // Convert opening to JSON array of hex strings
+    let json_opening = serde_json::to_string_pretty(
+        &opening
+            .iter()
+            .map(|node| node.encode_hex_upper::<String>())
+            .collect::<Vec<String>>(),
+    )
+    .expect("Could not convert opening to JSON");
+
+    if let Some(output_file) = output_file {
+        write_to_file(output_file, json_opening.as_bytes());
+    }
+
+    println!("Successfully generated!");
+    println!("Root: {}", tree.root().encode_hex_upper::<String>());
+    println!("Leaf index: {}", l);
+    println!("Opening: {}", json_opening);
+    println!("You can use root, leaf index and opening to generate your Proof");
+}
+
+/// Verify proof and print the result
+/// Returns `false` if the proof is not valid
+pub(crate) fn verify_proof(args: &Commands) -> Result<bool, Box<dyn Error>> {
+    // Get arguments from command
+    let (pp, vd, ok, proof, root, h, a, t, f, n, contract, chain_id) =
+        if let Commands::VerifyProof {
+            pp,
+            vd,
+            ok,
+            proof,
+            root,
+            h,
+            a,
+            t,
+            f,
+            n,
+            contract,
+            chain_id,
+        } = args
+        {
+            (pp, vd, ok, proof, root, h, a, t, f, n, contract, chain_id)
+        } else {
+            panic!("Wrong Command!")
+        };
+
+    // Read serialized proof
+    let proof = read_from_file(proof)
+        .try_into()
+        .expect("Proof has wrong size");
+
+    // Read and convert recipient address
+    let a = AccountId32::from_ss58check(a)
+        .expect("Could not convert input to AccountId32")
+        .into();
+
+    // Read and convert relayer address
+    let t = AccountId32::from_ss58check(t)
+        .expect("Could not convert input to AccountId32")
+        .into();
+
+    // Read and parse root and nullifier hash
+    let root: [u8; 32] = hex::decode(root).unwrap().try_into().unwrap();
+    let h: [u8; 32] = hex::decode(h).unwrap().try_into().unwrap();
+
+    // Compute domain of the contract and the chain
+    let d = parse_domain(contract, chain_id);
+
+    // Verify proof with public parameters or with verifier data
+    let result = match (pp, vd, ok) {
+        (Some(pp), _, _) => verify::<DEFAULT_DEPTH>(&read_pp(pp), h, root, a, t, *f, *n, d, &proof),
+        (None, Some(vd), Some(ok)) => verify_with_vd(
+            &read_from_file(vd),
+            read_from_file(ok)
+                .as_slice()
+                .try_into()
+                .expect("Opening key has wrong size"),
+            h,
+            root,
+            a,
+            t,
+            *f,
+            *n,
+            d,
+            &proof,
+        ),
+        _ => panic!("Either pp or vd and ok should be provided"),
+    };
+
+    match result {
+        Ok(()) => {
+            println!("Success! Proof is valid");
+            Ok(true)
+        }
+        // Keys which can't be read are not a verdict on the proof
+        Err(err @ SlushieProverError::KeyDeserialization) => Err(err.into()),
+        Err(err) => {
+            println!("Failure! Proof is not valid: {}", err);
+            Ok(false)
+        }
+    }
 }
 
 pub fn generate_commitment() {
@@ -65,6 +246,10 @@ pub fn generate_commitment() {
         nullifier_hash_bytes,
     } = plonk_prover::generate_commitment();
 
+    // Convert nullifier and randomness bytes to hex
+    let hex_nullifier = nullifier.encode_hex_upper::<String>();
+    let hex_randomness = randomness.encode_hex_upper::<String>();
+
     // Convert commitment bytes to hex
     let hex_commitment = commitment_bytes.encode_hex_upper::<String>();
 
@@ -72,8 +257,8 @@ pub fn generate_commitment() {
     let hex_nullifier_hash = nullifier_hash_bytes.encode_hex_upper::<String>();
 
     println!("Successfully generated! Please save this values:");
-    println!("Nullifier: {}", nullifier);
-    println!("Randomness: {}", randomness);
+    println!("Nullifier: {}", hex_nullifier);
+    println!("Randomness: {}", hex_randomness);
     println!("Commitment: {}", hex_commitment);
     println!("Nullifier Hash: {}", hex_nullifier_hash);
     println!("You can use:");
@@ -82,17 +267,39 @@ pub fn generate_commitment() {
     println!(" • nullifier hash to call withdraw contract method")
 }
 
+/// Generate note and print it with the commitment and nullifier hash
+pub fn generate_note(denomination: u128, network: &str) -> Result<(), Box<dyn Error>> {
+    let note = Note::generate(denomination, network)?;
+
+    // Convert commitment and nullifier hash bytes to hex
+    let hex_commitment = note.commitment().encode_hex_upper::<String>();
+    let hex_nullifier_hash = note.nullifier_hash().encode_hex_upper::<String>();
+
+    println!("Successfully generated! Please save this note:");
+    println!("{}", note);
+    println!("Commitment: {}", hex_commitment);
+    println!("Nullifier Hash: {}", hex_nullifier_hash);
+    println!("You can use:");
+    println!(" • commitment to call deposit contract method");
+    println!(" • note to generate your Proof");
+    println!(" • nullifier hash to call withdraw contract method");
+
+    Ok(())
+}
+
 /// Generate public parameters
-pub fn generate_pp(output_file: &str) {
-    let pp_bytes = generate_test_public_parameters().expect("Could not generate public parameters");
+pub fn generate_pp(output_file: &str) -> Result<(), Box<dyn Error>> {
+    let pp_bytes = generate_test_public_parameters()?;
 
     write_to_file(output_file, &pp_bytes);
 
     println!("Successfully generated!");
+
+    Ok(())
 }
 
 /// Generate prover data for provided public parameters
-pub fn generate_pd(args: &Commands) {
+pub fn generate_pd(args: &Commands) -> Result<(), Box<dyn Error>> {
     // Get arguments from command
     let (pp, output_pd, output_ck) = if let Commands::GenerateProverData {
         pp,
@@ -107,16 +314,18 @@ pub fn generate_pd(args: &Commands) {
 
     let pp_bytes = read_pp(pp);
 
-    let (pd, ck) = generate_prover_data(&pp_bytes).expect("Could not generate prover data");
+    let (pd, ck) = generate_prover_data(&pp_bytes)?;
 
     write_to_file(output_pd, &pd);
     write_to_file(output_ck, &ck);
 
     println!("Successfully generated!");
+
+    Ok(())
 }
 
 /// Generate verifier data for provided public parameters
-pub fn generate_vd(args: &Commands) {
+pub fn generate_vd(args: &Commands) -> Result<(), Box<dyn Error>> {
     // Get arguments from command
     let (pp, output_vd, output_ok) = if let Commands::GenerateVerifierData {
         pp,
@@ -131,10 +340,12 @@ pub fn generate_vd(args: &Commands) {
 
     let pp_bytes = read_pp(pp);
 
-    let (vd, ok) = generate_verifier_data(&pp_bytes).expect("Could not generate verifier data");
+    let (vd, ok) = generate_verifier_data(&pp_bytes)?;
 
     write_to_file(output_vd, &vd);
     write_to_file(output_ok, &ok);
 
     println!("Successfully generated!");
+
+    Ok(())
 }
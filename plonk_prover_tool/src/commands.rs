@@ -2,6 +2,7 @@ use clap::Parser;
 use clap::Subcommand;
 
 use crate::actions::*;
+use std::error::Error;
 
 /// The CLI Args struct.
 /// For the description of the params, please refer to the README.
@@ -18,6 +19,17 @@ pub enum Commands {
     /// Generate nullifier, randomness and commitment
     GenerateCommitment,
 
+    /// Generate note which keeps all secrets of a deposit
+    GenerateNote {
+        /// Deposit size
+        #[clap(short, long, value_parser)]
+        denomination: u128,
+
+        /// Network name
+        #[clap(short, long, value_parser)]
+        network: String,
+    },
+
     /// Generate public parameters
     GenerateTestPublicParameters {
         /// Path to serialized Public Parameters file
@@ -73,13 +85,27 @@ pub enum Commands {
         #[clap(long, value_parser)]
         o: String,
 
-        /// Nullifier generated in generate-commitment command
-        #[clap(long, value_parser)]
-        k: u32,
-
-        /// Randomness generated in generate-commitment command
+        /// Nullifier generated in generate-commitment command (32 bytes in hex)
+        #[clap(
+            long,
+            value_parser,
+            required_unless_present = "note",
+            conflicts_with = "note"
+        )]
+        k: Option<String>,
+
+        /// Randomness generated in generate-commitment command (32 bytes in hex)
+        #[clap(
+            long,
+            value_parser,
+            required_unless_present = "note",
+            conflicts_with = "note"
+        )]
+        r: Option<String>,
+
+        /// Note generated in generate-note command, replaces nullifier and randomness
         #[clap(long, value_parser)]
-        r: u32,
+        note: Option<String>,
 
         /// Recipient address in SS58
         #[clap(long, value_parser)]
@@ -93,20 +119,120 @@ pub enum Commands {
         #[clap(long, value_parser)]
         f: u64,
 
+        /// Denomination of the pool, taken from the note if not provided
+        #[clap(long, value_parser, required_unless_present = "note")]
+        n: Option<u128>,
+
+        /// Contract address in SS58, which the proof is bound to
+        #[clap(long, value_parser)]
+        contract: String,
+
+        /// Chain identifier, e.g. the genesis block hash (32 bytes in hex)
+        #[clap(long, value_parser)]
+        chain_id: String,
+
         /// Path to serialized proof file
         #[clap(long, value_parser)]
         output_file: String,
     },
+
+    /// Generate Merkle tree root, leaf index and opening for a commitment
+    TreeOpening {
+        /// Path to file with commitments in deposit order (hex lines or JSON array)
+        #[clap(long, value_parser)]
+        commitments: String,
+
+        /// Commitment which opening is generated (32 bytes in hex)
+        #[clap(long, value_parser)]
+        commitment: String,
+
+        /// Path to JSON file with generated opening, which can be used as `o` in generate-proof
+        #[clap(long, value_parser)]
+        output_file: Option<String>,
+    },
+
+    /// Verify Proof
+    VerifyProof {
+        /// Path to serialized Public Parameters file
+        #[clap(
+            long,
+            value_parser,
+            required_unless_present_all = &["vd", "ok"],
+            conflicts_with_all = &["vd", "ok"]
+        )]
+        pp: Option<String>,
+
+        /// Path to serialized verifier data
+        #[clap(long, value_parser, requires = "ok")]
+        vd: Option<String>,
+
+        /// Path to serialized opening key
+        #[clap(long, value_parser, requires = "vd")]
+        ok: Option<String>,
+
+        /// Path to serialized proof file
+        #[clap(long, value_parser)]
+        proof: String,
+
+        /// Root hash which was used in proof generation
+        #[clap(long, value_parser)]
+        root: String,
+
+        /// Nullifier hash (32 bytes in hex)
+        #[clap(long, value_parser)]
+        h: String,
+
+        /// Recipient address in SS58
+        #[clap(long, value_parser)]
+        a: String,
+
+        /// Relayer address in SS58
+        #[clap(long, value_parser)]
+        t: String,
+
+        /// Relayer fee
+        #[clap(long, value_parser)]
+        f: u64,
+
+        /// Denomination of the pool
+        #[clap(long, value_parser)]
+        n: u128,
+
+        /// Contract address in SS58, which the proof is bound to
+        #[clap(long, value_parser)]
+        contract: String,
+
+        /// Chain identifier, e.g. the genesis block hash (32 bytes in hex)
+        #[clap(long, value_parser)]
+        chain_id: String,
+    },
 }
 
 impl Commands {
-    pub fn do_action(&self) {
+    pub fn do_action(&self) -> Result<(), Box<dyn Error>> {
         match self {
-            Commands::GenerateCommitment => generate_commitment(),
+            Commands::GenerateCommitment => {
+                generate_commitment();
+                Ok(())
+            }
+            Commands::GenerateNote {
+                denomination,
+                network,
+            } => generate_note(*denomination, network),
             Commands::GenerateTestPublicParameters { output_pp } => generate_pp(output_pp),
             args @ Commands::GenerateProof { .. } => generate_proof(args),
             args @ Commands::GenerateVerifierData { .. } => generate_vd(args),
             args @ Commands::GenerateProverData { .. } => generate_pd(args),
+            args @ Commands::TreeOpening { .. } => {
+                tree_opening(args);
+                Ok(())
+            }
+            args @ Commands::VerifyProof { .. } => {
+                if !verify_proof(args)? {
+                    std::process::exit(1);
+                }
+                Ok(())
+            }
         }
     }
 }
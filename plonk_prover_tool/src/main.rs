@@ -7,68 +7,104 @@ mod actions;
 //size of array that contains keys
 use shared::constants::DEFAULT_DEPTH;
 
-fn main() {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Args::parse();
-    cli.command.do_action();
+    cli.command.do_action()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::actions::generate_proof;
+    use hex::ToHex;
+    use plonk_prover::hasher::Poseidon;
+    use plonk_prover::merkle_tree::MerkleTree;
+    use plonk_prover::note::Note;
+
+    use crate::actions::{generate_proof, generate_vd, tree_opening, verify_proof};
+    use crate::utils::{parse_commitments, parse_secret, parse_tree_openings, write_to_file};
 
     use super::*;
+
+    /// Contract and chain, which the test proofs are bound to
+    const CONTRACT: &str = "5FrfL6HXGbETXUBegkx7vz7LWQ5MNWRGNYE7PEm1w176nLpz";
+    const CHAIN_ID: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+    /// Note with the legacy secrets, which are used in the README examples
+    fn test_note() -> Note {
+        Note {
+            denomination: 100,
+            network: "local".to_string(),
+            nullifier: parse_secret("3141592653"),
+            randomness: parse_secret("1"),
+        }
+    }
+
+    /// Deposit the note commitment as the second leaf of a tree.
+    /// Returns the root and the opening as JSON array, which are accepted by generate-proof
+    fn deposit(note: &Note) -> (String, String) {
+        let commitments = [
+            plonk_prover::generate_commitment().commitment_bytes,
+            note.commitment(),
+        ];
+        let tree: MerkleTree<DEFAULT_DEPTH, Poseidon> = commitments.as_slice().try_into().unwrap();
+
+        let opening: Vec<String> = tree
+            .opening(1)
+            .unwrap()
+            .iter()
+            .map(|node| node.encode_hex_upper())
+            .collect();
+
+        (
+            tree.root().encode_hex_upper(),
+            serde_json::to_string(&opening).unwrap(),
+        )
+    }
+
     #[test]
     fn key_generated() {
+        let (root, opening) = deposit(&test_note());
+
         generate_proof(&Commands::GenerateProof {
             pp: "../public-parameters/pp-test".to_string(),
-            root: "0EDB120C1F24145A221C3B77D15ABC9959956FBE7E3B37832166CCB7ADE0CFCD".to_string(),
-            o: r#"[
-                "8B275561AB29C3EE4FDFF5AA9912AAC193B3A76F474B2C7B0AC60C0B69841AE2",
-                "AE603307AABE14B1685F623B22313F954277762A377CA4D64DBAFEC8F06DBECE",
-                "6BB536DE520D253D0A2C54FE5F186AA799527E29DA190A88234E4A46F7040829",
-                "79DD7DDF25DAD6F2BF0E15EA31C7CC3C1E9DF8AD7602E1A41987C41823BA5FC0",
-                "F8BCF2B724183F97F9231540F6BED2180B010F57DB38E7356BAEBE5FA7FE0592",
-                "B9ACD7A16684D97DC0640F27847861635A6AD6EC19737640096931ED7EFCF023",
-                "69FE3804090C7EB6A3DC26910406328E88AD9A1A3D17A21A488C0782D3CD1ADB",
-                "82E8D10EA7372F064EF182A7A582F8E4C75600AA5C6BED5C1ACFB46F3117B8BA",
-                "11FD66237D4AE27A0C9E254317DCACE75A1D0A58028C3F24019F41A23125AB9E",
-                "FE44E37502DA6662F83BF0A1AC0D87C5C6E48D5E729FD7DB517F3F9199A67AE9",
-                "51AE78857AD8DF490AE44880EA4C04E8B07F239F3FA6997F4C7FEFAE569E2E2D",
-                "0E741D218670C466CB8A6B1AE27302118FE3B18FB0E0815C2997E1EB96E85410",
-                "CBBB12B173FDF0EB4F29ACD9AD077F76E507AC01F7A218C80CAD31C702152499",
-                "42B6A37B934DF6CB4DC28C70ADC862ECA37A0C373E2FB39E35D9DC33437529F6",
-                "BAABDB3671917F9F7A95D1B6ACD0498911AFEF04EBD777953CB959BB8BDB2C1A",
-                "5345BF5B8DEDC82AF77B9C1E7479C850C2EE8980C3FC914848B10DD8CC2E09BD",
-                "BA3859D1ED676D0E4D8216CDBC0DD41058CFADCEBA6ABCB2691EF0EB48EA06E1",
-                "12FD1D45C71ACEB8AE52058263A7654A420347B75F098F1F6FC8F1266DEE082E",
-                "E6ECF20DEBA92629846283A3BF257D9E473422781184902D6BD7288B5A4EBD41",
-                "9ED838A9F6CB58FE464FA06DD5905D58AEDD9E4BD30ED0840E4828B57C5B7324"
-                ]"#
-            .to_string(),
+            root,
+            o: opening,
             l: 1,
-            k: 3141592653,
-            r: 1,
+            k: Some("3141592653".to_string()),
+            r: Some("1".to_string()),
+            note: None,
             a: "5DtCbNMGwhnP5wJ25Zv59wc5aj5uo3wYdr8536qSRxbvmLdK".to_string(),
             t: "5DtCbNMGwhnP5wJ25Zv59wc5aj5uo3wYdr8536qSRxbvmLdK".to_string(),
             f: 1,
+            n: Some(100),
+            contract: CONTRACT.to_string(),
+            chain_id: CHAIN_ID.to_string(),
             output_file: "test-proof".to_string(),
-        });
+        })
+        .unwrap();
     }
 
     #[test]
     fn key_generated_file_json() {
+        let (root, opening) = deposit(&test_note());
+        write_to_file("test-note-opening.json", opening.as_bytes());
+
         generate_proof(&Commands::GenerateProof {
             pp: "../public-parameters/pp-test".to_string(),
-            root: "0EDB120C1F24145A221C3B77D15ABC9959956FBE7E3B37832166CCB7ADE0CFCD".to_string(),
-            o: "test-json.json".to_string(),
+            root,
+            o: "test-note-opening.json".to_string(),
             l: 1,
-            k: 3141592653,
-            r: 1,
+            k: Some("3141592653".to_string()),
+            r: Some("1".to_string()),
+            note: None,
             a: "5DtCbNMGwhnP5wJ25Zv59wc5aj5uo3wYdr8536qSRxbvmLdK".to_string(),
             t: "5DtCbNMGwhnP5wJ25Zv59wc5aj5uo3wYdr8536qSRxbvmLdK".to_string(),
             f: 1,
+            n: Some(100),
+            contract: CONTRACT.to_string(),
+            chain_id: CHAIN_ID.to_string(),
             output_file: "test-proof".to_string(),
-        });
+        })
+        .unwrap();
     }
 
     #[test]
@@ -101,12 +137,159 @@ mod tests {
                 ]"#
             .to_string(),
             l: 1,
-            k: 3141592653,
-            r: 1,
+            k: Some("3141592653".to_string()),
+            r: Some("1".to_string()),
+            note: None,
+            a: "5DtCbNMGwhnP5wJ25Zv59wc5aj5uo3wYdr8536qSRxbvmLdK".to_string(),
+            t: "5DtCbNMGwhnP5wJ25Zv59wc5aj5uo3wYdr8536qSRxbvmLdK".to_string(),
+            f: 1,
+            n: Some(100),
+            contract: CONTRACT.to_string(),
+            chain_id: CHAIN_ID.to_string(),
+            output_file: "test-proof".to_string(),
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn legacy_secret_parsed_as_field_element() {
+        let legacy_secret = parse_secret("3141592653");
+        let hex_secret = parse_secret(&hex::encode(legacy_secret));
+
+        assert_eq!(legacy_secret, hex_secret);
+    }
+
+    #[test]
+    fn proof_generated_from_note() {
+        let note = test_note();
+        let (root, opening) = deposit(&note);
+
+        generate_proof(&Commands::GenerateProof {
+            pp: "../public-parameters/pp-test".to_string(),
+            root,
+            o: opening,
+            l: 1,
+            k: None,
+            r: None,
+            note: Some(note.to_string()),
             a: "5DtCbNMGwhnP5wJ25Zv59wc5aj5uo3wYdr8536qSRxbvmLdK".to_string(),
             t: "5DtCbNMGwhnP5wJ25Zv59wc5aj5uo3wYdr8536qSRxbvmLdK".to_string(),
             f: 1,
+            n: None,
+            contract: CONTRACT.to_string(),
+            chain_id: CHAIN_ID.to_string(),
             output_file: "test-proof".to_string(),
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn proof_verified() {
+        let note = test_note();
+        let (root, opening) = deposit(&note);
+        let address = "5DtCbNMGwhnP5wJ25Zv59wc5aj5uo3wYdr8536qSRxbvmLdK".to_string();
+
+        generate_proof(&Commands::GenerateProof {
+            pp: "../public-parameters/pp-test".to_string(),
+            root: root.clone(),
+            o: opening,
+            l: 1,
+            k: None,
+            r: None,
+            note: Some(note.to_string()),
+            a: address.clone(),
+            t: address.clone(),
+            f: 1,
+            n: None,
+            contract: CONTRACT.to_string(),
+            chain_id: CHAIN_ID.to_string(),
+            output_file: "test-verified-proof".to_string(),
+        })
+        .unwrap();
+
+        let verify_command =
+            |pp: Option<String>, vd: Option<String>, ok: Option<String>, f| Commands::VerifyProof {
+                pp,
+                vd,
+                ok,
+                proof: "test-verified-proof".to_string(),
+                root: root.clone(),
+                h: hex::encode(note.nullifier_hash()),
+                a: address.clone(),
+                t: address.clone(),
+                f,
+                n: note.denomination,
+                contract: CONTRACT.to_string(),
+                chain_id: CHAIN_ID.to_string(),
+            };
+
+        // Verification with public parameters
+        assert!(verify_proof(&verify_command(
+            Some("../public-parameters/pp-test".to_string()),
+            None,
+            None,
+            1
+        ))
+        .unwrap());
+
+        // Verification with verifier data, compiled for the current circuit
+        generate_vd(&Commands::GenerateVerifierData {
+            pp: "../public-parameters/pp-test".to_string(),
+            output_vd: "test-vd".to_string(),
+            output_ok: "test-opening-key".to_string(),
+        })
+        .unwrap();
+        assert!(verify_proof(&verify_command(
+            None,
+            Some("test-vd".to_string()),
+            Some("test-opening-key".to_string()),
+            1
+        ))
+        .unwrap());
+
+        // Proof is not valid for another fee
+        assert!(!verify_proof(&verify_command(
+            Some("../public-parameters/pp-test".to_string()),
+            None,
+            None,
+            2
+        ))
+        .unwrap());
+    }
+
+    #[test]
+    fn tree_opening_generated() {
+        let commitments: Vec<[u8; 32]> = (0..5)
+            .map(|_| plonk_prover::generate_commitment().commitment_bytes)
+            .collect();
+        let hex_commitments: Vec<String> = commitments
+            .iter()
+            .map(|c| c.encode_hex_upper::<String>())
+            .collect();
+
+        // Commitments can be written as hex lines or as JSON array
+        write_to_file(
+            "test-commitments.txt",
+            hex_commitments.join("\n").as_bytes(),
+        );
+        write_to_file(
+            "test-commitments.json",
+            serde_json::to_string(&hex_commitments).unwrap().as_bytes(),
+        );
+        assert_eq!(parse_commitments("test-commitments.txt"), commitments);
+        assert_eq!(parse_commitments("test-commitments.json"), commitments);
+
+        tree_opening(&Commands::TreeOpening {
+            commitments: "test-commitments.txt".to_string(),
+            commitment: hex_commitments[3].clone(),
+            output_file: Some("test-opening.json".to_string()),
         });
+
+        let tree: MerkleTree<DEFAULT_DEPTH, Poseidon> = commitments.as_slice().try_into().unwrap();
+
+        assert_eq!(
+            parse_tree_openings("test-opening.json"),
+            tree.opening(3).unwrap()
+        );
     }
 }
@@ -1,5 +1,8 @@
 use crate::DEFAULT_DEPTH;
+use plonk_prover::withdrawal_domain;
 use serde_json::Value;
+use shared::functions::legacy_secret_to_bytes;
+use sp_core::crypto::{AccountId32, Ss58Codec};
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
@@ -32,6 +35,61 @@ pub fn parse_tree_openings(o: &str) -> [[u8; 32]; DEFAULT_DEPTH] {
     }
 }
 
+/// This function parses commitments from file in deposit order.
+/// File contains either a JSON array of hex strings or a hex string on every line
+pub fn parse_commitments(path: &str) -> Vec<[u8; 32]> {
+    let content = std::fs::read_to_string(path).expect("Unable to read commitments from file");
+
+    if content.trim_start().starts_with('[') {
+        let commitments: Vec<String> =
+            serde_json::from_str(&content).expect("JSON was not well-formatted");
+
+        commitments.iter().map(|c| parse_commitment(c)).collect()
+    } else {
+        content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(parse_commitment)
+            .collect()
+    }
+}
+
+/// This function parses the commitment, encoded in hex as 32 bytes.
+pub fn parse_commitment(commitment: &str) -> [u8; 32] {
+    hex::decode(commitment.trim_start_matches("0x"))
+        .expect("Commitment should be in hex format")
+        .try_into()
+        .expect("Commitment should be 32 bytes long")
+}
+
+/// This function parses the nullifier or randomness, encoded in hex as 32 bytes.
+/// A decimal value is treated as a legacy 32-bit secret
+pub fn parse_secret(secret: &str) -> [u8; 32] {
+    if let Ok(legacy_secret) = secret.parse::<u32>() {
+        return legacy_secret_to_bytes(legacy_secret);
+    }
+
+    hex::decode(secret.trim_start_matches("0x"))
+        .expect("Secret should be in hex format")
+        .try_into()
+        .expect("Secret should be 32 bytes long")
+}
+
+/// This function computes the withdrawal domain from the contract address in SS58
+/// and the chain identifier, encoded in hex as 32 bytes.
+pub fn parse_domain(contract: &str, chain_id: &str) -> [u8; 32] {
+    let contract = AccountId32::from_ss58check(contract)
+        .expect("Could not convert input to AccountId32")
+        .into();
+    let chain_id = hex::decode(chain_id.trim_start_matches("0x"))
+        .expect("Chain id should be in hex format")
+        .try_into()
+        .expect("Chain id should be 32 bytes long");
+
+    withdrawal_domain(contract, chain_id)
+}
+
 /// Read public parameters from file
 pub fn read_pp(path: &str) -> Vec<u8> {
     let path = Path::new(path);
@@ -46,6 +104,18 @@ pub fn read_pp(path: &str) -> Vec<u8> {
     pp_bytes
 }
 
+/// Read serialized data from file
+pub fn read_from_file(path: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    File::open(path)
+        .unwrap()
+        .read_to_end(&mut bytes)
+        .expect("Unable to read file");
+
+    bytes
+}
+
 pub fn write_to_file(output_file: &str, content: &[u8]) {
     let mut output_file = File::create(output_file).expect("Unable to create file");
     output_file
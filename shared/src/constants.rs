@@ -3,3 +3,11 @@ pub const MAX_DEPTH: usize = 31;
 
 ///Depth which is used in Slushie mixer contract
 pub const DEFAULT_DEPTH: usize = 20;
+
+/// Scalar field modulus in little-endian limbs
+pub const SCALAR_MODULUS: [u64; 4] = [
+    0xffff_ffff_0000_0001,
+    0x53bd_a402_fffe_5bfe,
+    0x3339_d808_09a1_d805,
+    0x73ed_a753_299d_7d48,
+];
@@ -1,4 +1,7 @@
 use dusk_bls12_381::BlsScalar;
+
+use crate::constants::SCALAR_MODULUS;
+
 /// Cast bytes array to u64 array
 pub fn bytes_to_u64(bytes: [u8; 32]) -> [u64; 4] {
     let mut result = [0; 4];
@@ -25,6 +28,20 @@ pub fn u64_to_bytes(array: [u64; 4]) -> [u8; 32] {
     result
 }
 
+/// Check that bytes encode a value less than the scalar field modulus,
+/// so every field element has exactly one encoding
+pub fn is_canonical(bytes: [u8; 32]) -> bool {
+    let limbs = bytes_to_u64(bytes);
+
+    for (limb, modulus) in limbs.iter().zip(SCALAR_MODULUS.iter()).rev() {
+        if limb != modulus {
+            return limb < modulus;
+        }
+    }
+
+    false
+}
+
 pub fn bytes_to_scalar(bytes: [u8; 32]) -> BlsScalar {
     BlsScalar(bytes_to_u64(bytes))
 }
@@ -33,6 +50,48 @@ pub fn scalar_to_bytes(scalar: BlsScalar) -> [u8; 32] {
     u64_to_bytes(*scalar.internal_repr())
 }
 
+/// Convert an unsigned 128-bit value, such as a balance, to a field element
+pub fn u128_to_scalar(value: u128) -> BlsScalar {
+    BlsScalar::from_raw([value as u64, (value >> 64) as u64, 0, 0])
+}
+
+/// Convert 32 bytes of an account to two field elements of its 128-bit halves, the high one first.
+/// Unlike reduction modulo the field order, different accounts never get the same encoding
+pub fn account_to_scalars(account: [u8; 32]) -> [BlsScalar; 2] {
+    let mut high = [0; 16];
+    let mut low = [0; 16];
+    high.copy_from_slice(&account[..16]);
+    low.copy_from_slice(&account[16..]);
+
+    [
+        u128_to_scalar(u128::from_be_bytes(high)),
+        u128_to_scalar(u128::from_be_bytes(low)),
+    ]
+}
+
+/// Add the scalar field modulus to the bytes, the result is equal to them modulo the field order.
+/// Used in tests to check that such aliases are rejected or encoded differently.
+/// Panics if the sum doesn't fit into 32 bytes
+#[cfg(any(test, feature = "test-utils"))]
+pub fn add_modulus(bytes: [u8; 32]) -> [u8; 32] {
+    let mut limbs = bytes_to_u64(bytes);
+    let mut carry = 0;
+    for (limb, modulus) in limbs.iter_mut().zip(SCALAR_MODULUS) {
+        let sum = *limb as u128 + modulus as u128 + carry;
+        *limb = sum as u64;
+        carry = sum >> 64;
+    }
+    assert_eq!(carry, 0, "sum doesn't fit into 32 bytes");
+
+    u64_to_bytes(limbs)
+}
+
+/// Convert a legacy 32-bit nullifier or randomness to its serialized field element.
+/// Notes generated before secrets became full field elements stay valid this way
+pub fn legacy_secret_to_bytes(secret: u32) -> [u8; 32] {
+    scalar_to_bytes(BlsScalar::from(secret as u64))
+}
+
 #[cfg(test)]
 mod tests {
     use hex_literal::hex;
@@ -64,4 +123,52 @@ mod tests {
         let bytes = hex!("ad344e9209368feb18db5c71a2942319ad29a4913f293e4913f293e4fd4af322");
         assert_eq!(u64_to_bytes(bytes_to_u64(bytes)), bytes);
     }
+
+    #[test]
+    fn u128_to_scalar_matches_u64() {
+        assert_eq!(u128_to_scalar(13), BlsScalar::from(13));
+        assert_eq!(u128_to_scalar(u64::MAX as u128), BlsScalar::from(u64::MAX));
+        assert_eq!(
+            u128_to_scalar(1 << 64),
+            BlsScalar::from(u64::MAX) + BlsScalar::one()
+        );
+    }
+
+    #[test]
+    fn canonical_encoding() {
+        assert!(is_canonical([0; 32]));
+        assert!(is_canonical(scalar_to_bytes(-BlsScalar::one())));
+        assert!(!is_canonical(u64_to_bytes(SCALAR_MODULUS)));
+        assert!(!is_canonical([0xff; 32]));
+    }
+
+    #[test]
+    fn account_split_into_halves() {
+        let account = hex!("0000000000000000000000000000000d00000000000000000000000000000064");
+        assert_eq!(
+            account_to_scalars(account),
+            [BlsScalar::from(13), BlsScalar::from(100)]
+        );
+
+        // Accounts which are equal modulo the field order get different encodings
+        let account = [0x01; 32];
+        let alias = add_modulus(account);
+        assert_eq!(
+            BlsScalar::from_raw(bytes_to_u64(account)),
+            BlsScalar::from_raw(bytes_to_u64(alias))
+        );
+        assert_ne!(account_to_scalars(account), account_to_scalars(alias));
+    }
+
+    #[test]
+    fn legacy_secret_matches_scalar() {
+        assert_eq!(
+            bytes_to_scalar(legacy_secret_to_bytes(3141592653)),
+            BlsScalar::from(3141592653)
+        );
+        assert_eq!(
+            bytes_to_scalar(legacy_secret_to_bytes(0)),
+            BlsScalar::zero()
+        );
+    }
 }
@@ -4,3 +4,9 @@ pub use dusk_plonk::prelude::Proof;
 pub type PoseidonHash = [u8; 32];
 pub type SerializedProof = [u8; Proof::SIZE];
 pub type Pubkey = [u8; 32];
+/// Nullifier `k` as a serialized field element
+pub type Nullifier = [u8; 32];
+/// Randomness `r` as a serialized field element
+pub type Randomness = [u8; 32];
+/// Identifier of the chain which the contract is deployed to, e.g. the genesis block hash
+pub type ChainId = [u8; 32];
@@ -17,21 +17,35 @@
 //! Any function that modifies the state returns a `Result` type and does not changes the state
 //! if the `Error` occurs. The errors are defined as an `enum` type.
 //!
+//! ### Pools
+//!
+//! One contract serves several pools, every pool is identified by its `denomination`
+//! and has its own merkle tree and used nullifiers. Denominations are set in the constructor.
+//! Pools created with `new` mix the native balance, pools created with `new_psp22`
+//! mix the tokens of the given PSP22 contract and pools created with `new_psp34`
+//! mix any token of the given PSP34 collection.
+//!
 //! ### Deposit
 //!
-//! Tokens can only be deposited in a constant `deposit_size` amount.
+//! Tokens can only be deposited in a constant `denomination` amount of the chosen pool.
 //! Returns a MerkleTree root hash after the insertion of the nullifier.
 //!
 //! ### Withdraw
 //!
 //! Tokens can be withdrawn at any time, but for security reasons, it's better to wait some period say, 24 hours
 //! after deposit and before withdrawal to make it harder to track the token transfer.
-//! Tokens can be withdrawn only in a constant `deposit_size` amount by anyone who knows the nullifier and the root hash.
+//! Tokens can be withdrawn only in a constant `denomination` amount by anyone who knows the nullifier and the root hash.
+//! The proof is bound to the denomination, so it can't be used to withdraw from another pool.
+//! The relayer, who calls `withdraw`, receives the `fee` and the recipient receives `denomination - fee`.
+//! The proof is also bound to the domain of the contract address and the `chain_id` set in the constructor,
+//! so it can't be replayed against another deployment.
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
 use ink_lang as ink;
 
+mod psp22;
+mod psp34;
 mod tree;
 mod utils;
 
@@ -42,43 +56,235 @@ extern crate alloc;
 #[ink::contract]
 mod slushie {
     use super::*;
+    use crate::psp22::PSP22Error;
+    use crate::psp34::{Id, PSP34Error};
     use crate::tree::merkle_tree::{MerkleTree, MerkleTreeError, DEFAULT_ROOT_HISTORY_SIZE};
+    use alloc::vec::Vec;
+    use ink_primitives::KeyPtr;
+    #[cfg(feature = "std")]
+    use ink_storage::traits::StorageLayout;
+    use ink_storage::traits::{PackedLayout, SpreadAllocate, SpreadLayout};
     use plonk_prover::hasher::Poseidon;
+    use plonk_prover::{withdrawal_domain, SlushieVerifier};
     use shared::constants::DEFAULT_DEPTH;
+    use shared::public_types::ChainId;
     use utils::*;
 
     type PoseidonHash = [u8; 32];
 
+    /// Blake2x256 hash of the encoded verifying key
+    pub type KeyHash = [u8; 32];
+
+    /// Number of the merkle tree of the pool, a new tree is started when the previous one is full
+    pub type EpochId = u32;
+
+    type PoolTree = MerkleTree<Poseidon>;
+
+    /// Verifying key which the contract is instantiated with, the circuit has `DEFAULT_DEPTH`
     const SERIALIZED_VD: &[u8] = include_bytes!("../public-parameters/vd-test");
     const SERIALIZED_OPENING_KEY: &[u8; 240] =
         include_bytes!("../public-parameters/opening-key-test");
 
+    /// Time between proposing a new verifying key and its execution, 2 days in milliseconds
+    const DEFAULT_KEY_UPGRADE_DELAY: Timestamp = 2 * 24 * 60 * 60 * 1000;
+
+    /// Time after the key upgrade while the previous key is accepted, 1 day in milliseconds
+    const DEFAULT_KEY_GRACE_PERIOD: Timestamp = 24 * 60 * 60 * 1000;
+
+    /// Verifying key of the withdrawal circuit
+    #[derive(
+        Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, StorageLayout))]
+    pub struct VerifyingKey {
+        /// Serialized verifier data, it tells the depth of the merkle tree
+        /// which the circuit is compiled for
+        pub verifier_data: Vec<u8>,
+        /// Serialized opening key
+        pub opening_key: Vec<u8>,
+    }
+
+    /// Merkle tree parameters of the pools, set in the constructor
+    #[derive(
+        Debug,
+        Clone,
+        Copy,
+        PartialEq,
+        Eq,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+        SpreadAllocate,
+    )]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, StorageLayout))]
+    pub struct TreeConfig {
+        /// Depth of the merkle tree, should be in `1..=MAX_DEPTH`
+        pub depth: u8,
+        /// Number of the last roots which can be used for withdraw
+        pub root_history_size: u32,
+    }
+
+    impl Default for TreeConfig {
+        fn default() -> Self {
+            Self {
+                depth: DEFAULT_DEPTH as u8,
+                root_history_size: DEFAULT_ROOT_HISTORY_SIZE as u32,
+            }
+        }
+    }
+
+    /// Verifying key bundled with the contract for the tree depth, if there is one
+    fn bundled_verifying_key(depth: u8) -> Option<VerifyingKey> {
+        (depth as usize == DEFAULT_DEPTH).then(|| VerifyingKey {
+            verifier_data: SERIALIZED_VD.to_vec(),
+            opening_key: SERIALIZED_OPENING_KEY.to_vec(),
+        })
+    }
+
+    /// Asset which is deposited to and withdrawn from the pools
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, StorageLayout))]
+    pub enum Asset {
+        /// Native balance of the chain
+        Native,
+        /// PSP22 token with the given contract address
+        Psp22(AccountId),
+        /// Any token of the PSP34 collection with the given contract address
+        Psp34(AccountId),
+    }
+
+    impl SpreadAllocate for Asset {
+        fn allocate_spread(ptr: &mut KeyPtr) -> Self {
+            ptr.advance_by(<Self as SpreadLayout>::FOOTPRINT);
+            Asset::Native
+        }
+    }
+
+    /// Every pool is keyed by its denomination and has its own merkle tree and nullifiers
+    ///
+    /// When the merkle tree of the pool is full, it is replaced with an empty one
+    /// in the next epoch. Final root history of the full tree is archived,
+    /// so deposits of earlier epochs can still be withdrawn
     #[ink(storage)]
-    #[derive(ink_storage::traits::SpreadAllocate)]
+    #[derive(SpreadAllocate)]
     pub struct Slushie {
-        merkle_tree: MerkleTree<DEFAULT_DEPTH, DEFAULT_ROOT_HISTORY_SIZE, Poseidon>,
-        deposit_size: Balance,
-        used_nullifiers: ink_storage::Mapping<PoseidonHash, bool>,
+        owner: AccountId,
+        deposits_paused: bool,
+        withdrawals_paused: bool,
+        asset: Asset,
+        chain_id: ChainId,
+        domain: PoseidonHash,
+        tree_config: TreeConfig,
+        denominations: Vec<Balance>,
+        merkle_trees: ink_storage::Mapping<Balance, PoolTree>,
+        epochs: ink_storage::Mapping<Balance, EpochId>,
+        archived_root_histories: ink_storage::Mapping<(Balance, EpochId), Vec<PoseidonHash>>,
+        archived_roots: ink_storage::Mapping<(Balance, PoseidonHash), EpochId>,
+        used_nullifiers: ink_storage::Mapping<(Balance, PoseidonHash), bool>,
+        nft_inventory: ink_storage::Mapping<Balance, Vec<Id>>,
+        verifying_keys: ink_storage::Mapping<KeyHash, VerifyingKey>,
+        verifying_key_hash: KeyHash,
+        previous_key_hash: KeyHash,
+        previous_key_valid_until: Timestamp,
+        proposed_key_hash: KeyHash,
+        proposed_key_executable_at: Timestamp,
+        key_upgrade_delay: Timestamp,
+        key_grace_period: Timestamp,
     }
 
     /// Deposit event when the tokens deposited successfully
+    ///
+    /// Carries the epoch and the leaf index of the commitment and the root after insertion,
+    /// so the tree can be rebuilt off-chain without relying on the events order
     #[ink(event)]
     pub struct Deposited {
+        #[ink(topic)]
+        denomination: Balance,
+
+        epoch: EpochId,
+
         #[ink(topic)]
         hash: PoseidonHash,
 
+        leaf_index: u64,
+
+        root: PoseidonHash,
+
         timestamp: Timestamp,
     }
 
     /// Withdraw event when the tokens withdrawn successfully
+    ///
+    /// `epoch` is the epoch of the root which the proof was made for
     #[ink(event)]
     pub struct Withdrawn {
+        denomination: Balance,
+
+        epoch: EpochId,
+
         #[ink(topic)]
         hash: PoseidonHash,
 
+        #[ink(topic)]
+        recipient: AccountId,
+
+        #[ink(topic)]
+        relayer: AccountId,
+
+        fee: Balance,
+
         timestamp: Timestamp,
     }
 
+    /// Ownership event when the owner transferred the ownership
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        #[ink(topic)]
+        previous_owner: AccountId,
+
+        #[ink(topic)]
+        new_owner: AccountId,
+    }
+
+    /// Key proposal event when the owner proposed a new verifying key
+    #[ink(event)]
+    pub struct VerifyingKeyProposed {
+        #[ink(topic)]
+        key_hash: KeyHash,
+
+        executable_at: Timestamp,
+    }
+
+    /// Key proposal event when the owner cancelled the proposed verifying key
+    #[ink(event)]
+    pub struct VerifyingKeyProposalCancelled {
+        #[ink(topic)]
+        key_hash: KeyHash,
+    }
+
+    /// Key upgrade event when the proposed verifying key replaced the current one
+    #[ink(event)]
+    pub struct VerifyingKeyUpgraded {
+        #[ink(topic)]
+        key_hash: KeyHash,
+
+        #[ink(topic)]
+        previous_key_hash: KeyHash,
+
+        previous_key_valid_until: Timestamp,
+    }
+
+    /// Pause event when the owner paused or unpaused deposits or withdrawals
+    #[ink(event)]
+    pub struct PauseChanged {
+        deposits_paused: bool,
+
+        withdrawals_paused: bool,
+    }
+
     /// Errors which my be returned from the smart contract
     #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
@@ -93,6 +299,24 @@ mod slushie {
         UnknownRoot,
         VerificationProofFailed,
         TransferFailed,
+        FeeExceedsDepositSize,
+        UnknownPool,
+        TokenCallFailed,
+        TokenTransferFailed(PSP22Error),
+        NftTransferFailed(PSP34Error),
+        InvalidAsset,
+        NotOwner,
+        DepositsPaused,
+        WithdrawalsPaused,
+        InvalidVerifyingKey,
+        NoKeyProposal,
+        KeyUpgradeTooEarly,
+        InvalidKeyUpgradeDelay,
+        EmptyBatch,
+        DuplicateNullifier,
+        UnknownEpoch,
+        NotNftOwner,
+        InvalidRootHistorySize,
     }
 
     impl From<MerkleTreeError> for Error {
@@ -101,12 +325,32 @@ mod slushie {
                 MerkleTreeError::MerkleTreeIsFull => Error::MerkleTreeIsFull,
                 MerkleTreeError::DepthTooLong => Error::MerkleTreeInvalidDepth,
                 MerkleTreeError::DepthIsZero => Error::MerkleTreeInvalidDepth,
+                MerkleTreeError::RootHistoryIsEmpty => Error::InvalidRootHistorySize,
+                MerkleTreeError::RootHistoryTooLong => Error::InvalidRootHistorySize,
+            }
+        }
+    }
+
+    impl From<TokenError<PSP22Error>> for Error {
+        fn from(err: TokenError<PSP22Error>) -> Self {
+            match err {
+                TokenError::CallFailed => Error::TokenCallFailed,
+                TokenError::Rejected(err) => Error::TokenTransferFailed(err),
+            }
+        }
+    }
+
+    impl From<TokenError<PSP34Error>> for Error {
+        fn from(err: TokenError<PSP34Error>) -> Self {
+            match err {
+                TokenError::CallFailed => Error::TokenCallFailed,
+                TokenError::Rejected(err) => Error::NftTransferFailed(err),
             }
         }
     }
 
     /// Struct for public inputs for withdraw method
-    #[derive(scale::Encode, scale::Decode)]
+    #[derive(Clone, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct PublicInputs {
         pub nullifier_hash: PoseidonHash,
@@ -118,205 +362,1955 @@ mod slushie {
 
     pub type Result<T> = core::result::Result<T, Error>;
 
+    /// Pool tree which the commitments are inserted into before it is saved
+    struct PendingTree {
+        epoch: EpochId,
+        merkle_tree: PoolTree,
+        /// Final root histories of the trees filled during the insertion
+        archived: Vec<(EpochId, Vec<PoseidonHash>)>,
+    }
+
+    impl PendingTree {
+        /// Archive the full merkle tree and continue with an empty one in the next epoch
+        fn roll_over(&mut self) -> Result<()> {
+            let empty_tree = PoolTree::new(
+                self.merkle_tree.depth(),
+                self.merkle_tree.root_history_size(),
+            )?;
+            let full_tree = core::mem::replace(&mut self.merkle_tree, empty_tree);
+            self.archived.push((self.epoch, full_tree.get_roots()));
+            self.epoch += 1;
+
+            Ok(())
+        }
+    }
+
     impl Slushie {
         /// create a new Slushie contract
         ///
-        /// Takes the deposit_size Balance amount
-        /// so the users can deposit and withdraw
-        /// only in a fixed amount of tokens.
+        /// Takes the list of denominations, every one of them
+        /// opens a pool where the users can deposit and withdraw
+        /// only in this fixed amount of tokens.
         /// Can be set only when the smart contract
         /// instantiated.
+        ///
+        /// `chain_id` identifies the chain, e.g. its genesis block hash.
+        /// Together with the contract address it gives the domain of the withdrawal proofs.
+        #[ink(constructor)]
+        pub fn new(denominations: Vec<Balance>, chain_id: ChainId) -> Self {
+            Self::new_with_tree_config(
+                Asset::Native,
+                denominations,
+                TreeConfig::default(),
+                None,
+                None,
+                chain_id,
+            )
+        }
+
+        /// create a new Slushie contract with pools of the PSP22 token
+        ///
+        /// Same as `new`, but the pools accept only the tokens of the `token` contract.
+        /// Deposit pulls the tokens with `transfer_from`, so the depositor should
+        /// approve the Slushie contract to spend the `denomination` amount before.
+        #[ink(constructor)]
+        pub fn new_psp22(denominations: Vec<Balance>, token: AccountId, chain_id: ChainId) -> Self {
+            Self::new_with_tree_config(
+                Asset::Psp22(token),
+                denominations,
+                TreeConfig::default(),
+                None,
+                None,
+                chain_id,
+            )
+        }
+
+        /// create a new Slushie contract with pools of the PSP34 collection
+        ///
+        /// The pools mix any token of the `collection`, see `deposit_nft`.
+        /// NFT can't be split to pay the relayer, so the `denomination` of such pool
+        /// is the native balance which is deposited together with the token
+        /// and pays `denomination - fee` to the recipient and `fee` to the relayer.
+        #[ink(constructor)]
+        pub fn new_psp34(
+            denominations: Vec<Balance>,
+            collection: AccountId,
+            chain_id: ChainId,
+        ) -> Self {
+            Self::new_with_tree_config(
+                Asset::Psp34(collection),
+                denominations,
+                TreeConfig::default(),
+                None,
+                None,
+                chain_id,
+            )
+        }
+
+        /// create a new Slushie contract with the merkle tree parameters
+        ///
+        /// Same as the other constructors, but the pools of the `asset` use merkle trees
+        /// of `tree_config.depth` with `tree_config.root_history_size` last roots.
+        /// `verifying_key` should be compiled for the same depth, the bundled key
+        /// is selected if it is not provided. `key_grace_period` is the time after
+        /// a verifying key upgrade while the previous key is accepted, 1 day if it is not provided.
+        /// Panics with the `DepthTooLong` or
+        /// `DepthIsZero` merkle tree error if the depth is not in `1..=MAX_DEPTH`,
+        /// and with the `RootHistoryIsEmpty` or `RootHistoryTooLong` error
+        /// if the root history size is not in `1..=MAX_ROOT_HISTORY_SIZE`.
         #[ink(constructor)]
-        pub fn new(deposit_size: Balance) -> Self {
+        pub fn new_with_tree_config(
+            asset: Asset,
+            denominations: Vec<Balance>,
+            tree_config: TreeConfig,
+            verifying_key: Option<VerifyingKey>,
+            key_grace_period: Option<Timestamp>,
+            chain_id: ChainId,
+        ) -> Self {
+            let empty_tree = PoolTree::new(
+                tree_config.depth as usize,
+                tree_config.root_history_size as usize,
+            )
+            .expect("tree config should be valid");
+
+            let verifying_key = verifying_key
+                .or_else(|| bundled_verifying_key(tree_config.depth))
+                .expect("verifying key should be provided for the tree depth");
+
             ink::utils::initialize_contract(|me: &mut Self| {
-                *me = Self {
-                    merkle_tree:
-                        MerkleTree::<DEFAULT_DEPTH, DEFAULT_ROOT_HISTORY_SIZE, Poseidon>::new()
-                            .unwrap(),
-                    deposit_size,
-                    used_nullifiers: Default::default(),
-                };
+                me.owner = Self::env().caller();
+                me.asset = asset;
+                me.chain_id = chain_id;
+                me.domain = withdrawal_domain(*Self::env().account_id().as_ref(), chain_id);
+                me.tree_config = tree_config;
+                me.check_verifying_key(&verifying_key)
+                    .expect("verifying key should match the tree depth");
+                me.verifying_key_hash = me.store_verifying_key(&verifying_key);
+                me.key_upgrade_delay = DEFAULT_KEY_UPGRADE_DELAY;
+                me.key_grace_period = key_grace_period.unwrap_or(DEFAULT_KEY_GRACE_PERIOD);
+
+                for denomination in &denominations {
+                    assert!(*denomination > 0, "denomination should be positive");
+                    assert!(
+                        me.merkle_trees.get(denomination).is_none(),
+                        "denominations should be unique"
+                    );
+
+                    me.merkle_trees.insert(denomination, &empty_tree);
+                }
+
+                me.denominations = denominations;
             })
         }
 
-        /// Deposit a fixed amount of tokens into mixer
+        /// Deposit a fixed amount of tokens into the pool
+        ///
+        /// Returns the merkle_tree root hash after insertion
+        #[ink(message, payable)]
+        pub fn deposit(
+            &mut self,
+            denomination: Balance,
+            commitment: PoseidonHash,
+        ) -> Result<PoseidonHash> {
+            self.deposit_commitments(denomination, &[commitment])
+        }
+
+        /// Deposit several commitments into the pool in one call
+        ///
+        /// Transferred value should be equal to `denomination` for every commitment,
+        /// the PSP22 pool pulls this amount of tokens from the caller.
+        /// Either all commitments are inserted or none of them. If the tree fills up,
+        /// the rest of the commitments are inserted into the tree of the next epoch.
+        ///
+        /// Returns the merkle_tree root hash after the last insertion
+        #[ink(message, payable)]
+        pub fn deposit_batch(
+            &mut self,
+            denomination: Balance,
+            commitments: Vec<PoseidonHash>,
+        ) -> Result<PoseidonHash> {
+            if commitments.is_empty() {
+                return Err(Error::EmptyBatch);
+            }
+
+            self.deposit_commitments(denomination, &commitments)
+        }
+
+        /// Deposit the token `id` of the PSP34 collection into the pool
+        ///
+        /// The contract takes custody of the token, so the caller should own it
+        /// and approve the Slushie contract to transfer it before.
+        /// Transferred value should be equal to the pool `denomination`, which pays
+        /// the relayer fee on withdraw.
         ///
         /// Returns the merkle_tree root hash after insertion
         #[ink(message, payable)]
-        pub fn deposit(&mut self, commitment: PoseidonHash) -> Result<PoseidonHash> {
+        pub fn deposit_nft(
+            &mut self,
+            denomination: Balance,
+            commitment: PoseidonHash,
+            id: Id,
+        ) -> Result<PoseidonHash> {
+            self.ensure_deposits_not_paused()?;
+
+            let collection = match self.asset {
+                Asset::Psp34(collection) => collection,
+                _ => return Err(Error::InvalidAsset),
+            };
+
+            let mut pending = self.pending_tree(denomination)?;
+
             // Check that transferred value equal to deposit size
-            if self.env().transferred_value() != self.deposit_size {
+            if self.env().transferred_value() != denomination {
                 return Err(Error::InvalidTransferredAmount);
             }
 
-            // Save commitment to the merkle tree
-            self.merkle_tree.insert(commitment)?;
+            // Approval to the contract doesn't let anyone else deposit the token
+            // with their own commitment
+            if psp34::owner_of(collection, id.clone())? != Some(self.env().caller()) {
+                return Err(Error::NotNftOwner);
+            }
+
+            let events = self.insert_commitments(denomination, &mut pending, &[commitment])?;
 
-            // Emit Deposited Event
-            self.env().emit_event(Deposited {
-                hash: commitment,
-                timestamp: self.env().block_timestamp(),
-            });
+            // Take custody of the token
+            psp34::transfer(collection, self.env().account_id(), id.clone())?;
+
+            let mut inventory = self.nft_inventory.get(denomination).unwrap_or_default();
+            inventory.push(id);
+            self.nft_inventory.insert(denomination, &inventory);
+
+            Ok(self.save_deposits(denomination, pending, events))
+        }
+
+        /// Deposit `denomination` of the pools asset for every commitment
+        fn deposit_commitments(
+            &mut self,
+            denomination: Balance,
+            commitments: &[PoseidonHash],
+        ) -> Result<PoseidonHash> {
+            self.ensure_deposits_not_paused()?;
+
+            let mut pending = self.pending_tree(denomination)?;
+
+            let amount = denomination
+                .checked_mul(commitments.len() as Balance)
+                .ok_or(Error::InvalidTransferredAmount)?;
+
+            match self.asset {
+                Asset::Native => {
+                    // Check that transferred value equal to deposit size of all commitments
+                    if self.env().transferred_value() != amount {
+                        return Err(Error::InvalidTransferredAmount);
+                    }
+                }
+                Asset::Psp22(_) => {
+                    // Native value can't be deposited to the token pool
+                    if self.env().transferred_value() != 0 {
+                        return Err(Error::InvalidTransferredAmount);
+                    }
+                }
+                // NFT is deposited with `deposit_nft`
+                Asset::Psp34(_) => return Err(Error::InvalidAsset),
+            }
+
+            // Tree is saved only after all commitments are inserted
+            let events = self.insert_commitments(denomination, &mut pending, commitments)?;
+
+            if let Asset::Psp22(token) = self.asset {
+                // Pull deposit size of all commitments from the caller
+                psp22::transfer_from(token, self.env().caller(), self.env().account_id(), amount)?;
+            }
+
+            Ok(self.save_deposits(denomination, pending, events))
+        }
+
+        /// Insert commitments into the merkle tree of the pool,
+        /// rolling over to the next epoch when the tree is full
+        ///
+        /// The tree is not saved, returns Deposited events for the inserted leaves
+        fn insert_commitments(
+            &self,
+            denomination: Balance,
+            pending: &mut PendingTree,
+            commitments: &[PoseidonHash],
+        ) -> Result<Vec<Deposited>> {
+            let timestamp = self.env().block_timestamp();
+
+            commitments
+                .iter()
+                .map(|&commitment| -> Result<Deposited> {
+                    if pending.merkle_tree.is_full() {
+                        pending.roll_over()?;
+                    }
+
+                    let leaf_index = pending.merkle_tree.insert(commitment)?;
+
+                    Ok(Deposited {
+                        denomination,
+                        epoch: pending.epoch,
+                        hash: commitment,
+                        leaf_index: leaf_index as u64,
+                        root: pending.merkle_tree.get_last_root() as PoseidonHash,
+                        timestamp,
+                    })
+                })
+                .collect()
+        }
 
-            Ok(self.merkle_tree.get_last_root() as PoseidonHash)
+        /// Save the merkle tree of the pool with the archived root histories
+        /// and emit Deposited events
+        ///
+        /// Returns the merkle_tree root hash after the last insertion
+        fn save_deposits(
+            &mut self,
+            denomination: Balance,
+            pending: PendingTree,
+            events: Vec<Deposited>,
+        ) -> PoseidonHash {
+            for (epoch, roots) in pending.archived {
+                for root in &roots {
+                    self.archived_roots.insert((denomination, *root), &epoch);
+                }
+
+                self.archived_root_histories
+                    .insert((denomination, epoch), &roots);
+            }
+
+            self.epochs.insert(denomination, &pending.epoch);
+            self.merkle_trees.insert(denomination, &pending.merkle_tree);
+
+            for event in events {
+                self.env().emit_event(event);
+            }
+
+            pending.merkle_tree.get_last_root() as PoseidonHash
         }
 
-        /// Withdraw a fixed amount of tokens from the mixer
+        /// Withdraw a fixed amount of tokens from the pool
         ///
         /// Can be withdrawn by anyone who knows the nullifier and the correct root hash
         #[ink(message)]
-        pub fn withdraw(&mut self, public_inputs: PublicInputs) -> Result<()> {
-            // Check that provided root is known
-            if !self.merkle_tree.is_known_root(public_inputs.root) {
-                return Err(Error::UnknownRoot);
+        pub fn withdraw(
+            &mut self,
+            denomination: Balance,
+            public_inputs: PublicInputs,
+        ) -> Result<()> {
+            if self.withdrawals_paused {
+                return Err(Error::WithdrawalsPaused);
             }
 
-            // Check that contract has enough balance
-            if self.env().balance() < self.deposit_size {
-                return Err(Error::InsufficientFunds);
+            let merkle_tree = self.pool_tree(denomination)?;
+            let verifiers = self.accepted_verifiers();
+
+            // Caller is the relayer who receives the fee
+            let relayer = self.env().caller();
+
+            let epoch = self.validate_withdrawal(
+                denomination,
+                &merkle_tree,
+                &verifiers,
+                relayer,
+                &public_inputs,
+            )?;
+
+            self.complete_withdrawal(denomination, epoch, relayer, &public_inputs)
+        }
+
+        /// Withdraw several deposits from the pool in one call
+        ///
+        /// Every withdrawal is checked as in `withdraw` and all proofs are verified
+        /// before any withdrawal is paid, the caller receives all fees.
+        /// Batch with duplicated nullifier hashes is rejected.
+        ///
+        /// If `atomic` is set, either all withdrawals are paid or none of them:
+        /// the first invalid withdrawal is returned as an error.
+        /// Otherwise, valid withdrawals are paid and the result for every withdrawal
+        /// is returned in the same order. Funds are checked before every payout,
+        /// but a transfer which fails for another reason, e.g. the existential deposit,
+        /// still traps and reverts the whole batch.
+        #[ink(message)]
+        pub fn withdraw_batch(
+            &mut self,
+            denomination: Balance,
+            batch: Vec<PublicInputs>,
+            atomic: bool,
+        ) -> Result<Vec<Result<()>>> {
+            if self.withdrawals_paused {
+                return Err(Error::WithdrawalsPaused);
             }
 
-            // Check that provided nullifier hash is not used
-            if self
-                .used_nullifiers
-                .get(public_inputs.nullifier_hash)
-                .is_some()
-            {
-                return Err(Error::NullifierAlreadyUsed);
+            if batch.is_empty() {
+                return Err(Error::EmptyBatch);
             }
 
-            // Check provided proof
-            if !check_proof(
-                SERIALIZED_VD,
-                SERIALIZED_OPENING_KEY,
-                &public_inputs,
-                self.env().caller(),
-            ) {
-                return Err(Error::VerificationProofFailed);
+            // Check that nullifier hashes are unique within the batch
+            for (i, public_inputs) in batch.iter().enumerate() {
+                if batch[..i]
+                    .iter()
+                    .any(|other| other.nullifier_hash == public_inputs.nullifier_hash)
+                {
+                    return Err(Error::DuplicateNullifier);
+                }
             }
 
-            // Transfer to recipient
-            self.env()
-                .transfer(public_inputs.recipient, self.deposit_size)
-                .map_err(|_| Error::TransferFailed)?;
+            let merkle_tree = self.pool_tree(denomination)?;
+            let verifiers = self.accepted_verifiers();
+            let relayer = self.env().caller();
+
+            // Check all withdrawals and verify all proofs before paying any of them
+            let proofs_accepted =
+                check_proofs(&verifiers, &batch, relayer, denomination, self.domain);
+            let validated = batch
+                .iter()
+                .zip(proofs_accepted)
+                .map(|(public_inputs, proof_accepted)| {
+                    let epoch = self.check_withdrawal(denomination, &merkle_tree, public_inputs)?;
+
+                    if !proof_accepted {
+                        return Err(Error::VerificationProofFailed);
+                    }
+
+                    Ok(epoch)
+                })
+                .collect::<Vec<Result<EpochId>>>();
+
+            if atomic {
+                let epochs = validated.into_iter().collect::<Result<Vec<EpochId>>>()?;
+
+                self.ensure_can_pay(denomination, batch.len())?;
+
+                for (i, (public_inputs, epoch)) in batch.iter().zip(epochs).enumerate() {
+                    if let Err(err) =
+                        self.complete_withdrawal(denomination, epoch, relayer, public_inputs)
+                    {
+                        // All withdrawals are validated and the funds are checked,
+                        // only a failed transfer can get here
+                        if i > 0 {
+                            panic!("withdrawal payout failed after paying the previous ones");
+                        }
+
+                        return Err(err);
+                    }
+                }
+
+                return Ok(batch.iter().map(|_| Ok(())).collect());
+            }
 
-            // Save used nullifier hash
-            self.used_nullifiers
-                .insert(public_inputs.nullifier_hash, &true);
+            Ok(batch
+                .iter()
+                .zip(validated)
+                .map(|(public_inputs, epoch)| {
+                    let epoch = epoch?;
 
-            // Emit Withdrawn Event
-            self.env().emit_event(Withdrawn {
-                hash: public_inputs.nullifier_hash,
-                timestamp: self.env().block_timestamp(),
+                    // Previous items have been paid, so the funds are checked again,
+                    // otherwise a partly paid withdrawal would trap and revert the whole batch
+                    self.ensure_can_pay(denomination, 1)?;
+
+                    self.complete_withdrawal(denomination, epoch, relayer, public_inputs)
+                })
+                .collect())
+        }
+
+        /// Returns the merkle_tree root hash of the pool in the current epoch
+        #[ink(message)]
+        pub fn get_root_hash(&self, denomination: Balance) -> Result<PoseidonHash> {
+            Ok(self.pool_tree(denomination)?.get_last_root() as PoseidonHash)
+        }
+
+        /// Returns the asset of the pools
+        #[ink(message)]
+        pub fn asset(&self) -> Asset {
+            self.asset
+        }
+
+        /// Transfer the ownership of the contract to `new_owner`
+        ///
+        /// Can be called only by the owner
+        #[ink(message)]
+        pub fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+
+            let previous_owner = self.owner;
+            self.owner = new_owner;
+
+            self.env().emit_event(OwnershipTransferred {
+                previous_owner,
+                new_owner,
             });
 
             Ok(())
         }
 
-        /// Returns the merkle_tree root hash
+        /// Pause or unpause deposits to all pools
+        ///
+        /// Can be called only by the owner. Withdrawals stay enabled,
+        /// so users can exit while deposits are paused
         #[ink(message)]
-        pub fn get_root_hash(&self) -> PoseidonHash {
-            self.merkle_tree.get_last_root() as PoseidonHash
-        }
-    }
+        pub fn set_deposits_paused(&mut self, paused: bool) -> Result<()> {
+            self.ensure_owner()?;
 
-    /// Unit tests
-    #[cfg(test)]
-    mod tests {
-        use super::*;
-        use hex_literal::hex;
+            self.deposits_paused = paused;
+            self.emit_pause_changed();
 
-        const SERIALIZED_PUBLIC_PARAMETERS: &[u8] = include_bytes!("../public-parameters/pp-test");
+            Ok(())
+        }
 
-        /// Imports `ink_lang` so we can use `#[ink::test]`.
-        use ink_lang as ink;
-        use plonk_prover::{prove, GeneratedCommitment};
+        /// Pause or unpause withdrawals from all pools
+        ///
+        /// Can be called only by the owner
+        #[ink(message)]
+        pub fn set_withdrawals_paused(&mut self, paused: bool) -> Result<()> {
+            self.ensure_owner()?;
 
-        #[ink::test]
-        fn test_constructor() {
-            let slushie: Slushie = Slushie::new(13);
+            self.withdrawals_paused = paused;
+            self.emit_pause_changed();
 
-            assert_eq!(slushie.deposit_size, 13 as Balance);
-            assert_eq!(
-                slushie.merkle_tree,
-                MerkleTree::<DEFAULT_DEPTH, DEFAULT_ROOT_HISTORY_SIZE, Poseidon>::new().unwrap()
-            );
+            Ok(())
         }
 
-        /// can deposit funds with a proper `deposit_size`
-        #[ink::test]
-        fn deposit_works() {
-            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
-            let mut slushie: Slushie = Slushie::new(13);
-            let commitment: PoseidonHash =
-                hex!("0001020304050607 08090a0b0c0d0e0f 0001020304050607 08090a0b0c0d0e0f");
+        /// Propose a new verifying key
+        ///
+        /// Can be called only by the owner. The key can be executed with
+        /// `execute_verifying_key_upgrade` after the key upgrade delay.
+        /// A new proposal replaces the previous one.
+        ///
+        /// Returns the hash of the proposed key, or `InvalidVerifyingKey` error
+        /// if the key can't be deserialized or is compiled for another tree depth
+        #[ink(message)]
+        pub fn propose_verifying_key(&mut self, key: VerifyingKey) -> Result<KeyHash> {
+            self.ensure_owner()?;
+            self.check_verifying_key(&key)?;
 
-            let initial_root_hash = slushie.get_root_hash();
+            let replaced_key_hash = self.proposed_key_hash;
+            let key_hash = self.store_verifying_key(&key);
+            let executable_at = self.env().block_timestamp() + self.key_upgrade_delay;
 
-            ink_env::test::set_caller::<Environment>(accounts.bob);
-            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
-            let res = slushie.deposit(commitment);
-            assert!(res.is_ok());
+            self.proposed_key_hash = key_hash;
+            self.proposed_key_executable_at = executable_at;
+            self.remove_verifying_key(replaced_key_hash);
 
-            let resulting_root_hash = slushie.get_root_hash();
-            assert_ne!(initial_root_hash, resulting_root_hash);
+            self.env().emit_event(VerifyingKeyProposed {
+                key_hash,
+                executable_at,
+            });
+
+            Ok(key_hash)
         }
 
-        /// can't deposit funds with an invalid `deposit_size`
-        #[ink::test]
-        fn deposit_invalid_amount_fails() {
-            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
-            let deposit_size = 13;
-            let invalid_deposit_size = 55;
-            let mut slushie: Slushie = Slushie::new(deposit_size);
-            let commitment: PoseidonHash =
-                hex!("0001020304050607 08090a0b0c0d0e0f 0001020304050607 08090a0b0c0d0e0f");
+        /// Cancel the proposed verifying key
+        ///
+        /// Can be called only by the owner
+        #[ink(message)]
+        pub fn cancel_verifying_key_proposal(&mut self) -> Result<()> {
+            self.ensure_owner()?;
 
-            let initial_root_hash = slushie.get_root_hash();
+            let key_hash = self.proposed_key_hash()?;
+            self.proposed_key_hash = KeyHash::default();
+            self.remove_verifying_key(key_hash);
 
-            ink_env::test::set_caller::<Environment>(accounts.bob);
-            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(
-                invalid_deposit_size,
-            );
-            let res = slushie.deposit(commitment);
-            assert_eq!(res.unwrap_err(), Error::InvalidTransferredAmount);
+            self.env()
+                .emit_event(VerifyingKeyProposalCancelled { key_hash });
 
-            let resulting_root_hash = slushie.get_root_hash();
-            assert_eq!(initial_root_hash, resulting_root_hash);
+            Ok(())
         }
 
-        /// can't deposit funds if account doesn't have enough money
+        /// Replace the current verifying key with the proposed one
         ///
-        /// this case shouldn't be tested cause is a pallete, which
-        /// checks the sufficient amount of funds
+        /// Can be called only by the owner after the key upgrade delay.
+        /// The previous key is still accepted during the grace period,
+        /// so proofs generated before the upgrade can be used.
+        #[ink(message)]
+        pub fn execute_verifying_key_upgrade(&mut self) -> Result<()> {
+            self.ensure_owner()?;
 
-        /// - can withdraw funds with a proper deposit_size and hash
-        #[ink::test]
-        fn withdraw_works() {
-            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
-            let deposit_size: Balance = 13;
-            let mut slushie: Slushie = Slushie::new(deposit_size);
+            let key_hash = self.proposed_key_hash()?;
+            let now = self.env().block_timestamp();
+            if now < self.proposed_key_executable_at {
+                return Err(Error::KeyUpgradeTooEarly);
+            }
 
-            let GeneratedCommitment {
-                nullifier,
-                randomness,
-                commitment_bytes,
-                nullifier_hash_bytes,
-            } = plonk_prover::generate_commitment();
+            let replaced_key_hash = self.previous_key_hash;
+            self.previous_key_hash = self.verifying_key_hash;
+            self.previous_key_valid_until = now + self.key_grace_period;
+            self.verifying_key_hash = key_hash;
+            self.proposed_key_hash = KeyHash::default();
+            self.remove_verifying_key(replaced_key_hash);
+
+            self.env().emit_event(VerifyingKeyUpgraded {
+                key_hash,
+                previous_key_hash: self.previous_key_hash,
+                previous_key_valid_until: self.previous_key_valid_until,
+            });
 
-            let hash: PoseidonHash = commitment_bytes;
+            Ok(())
+        }
 
-            ink_env::test::set_caller::<Environment>(accounts.alice);
-            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(deposit_size);
-            let res = slushie.deposit(hash);
-            assert!(res.is_ok());
+        /// Set the time between proposing and executing a verifying key
+        ///
+        /// Can be called only by the owner. The delay can only be increased,
+        /// so it can't be used to skip the time lock
+        #[ink(message)]
+        pub fn set_key_upgrade_delay(&mut self, delay: Timestamp) -> Result<()> {
+            self.ensure_owner()?;
 
-            let resulting_root_hash = slushie.get_root_hash();
+            if delay < self.key_upgrade_delay {
+                return Err(Error::InvalidKeyUpgradeDelay);
+            }
 
-            let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> =
-                (&[commitment_bytes][..]).try_into().unwrap();
+            self.key_upgrade_delay = delay;
 
-            let tree_opening = tree.get_opening(0).unwrap();
+            Ok(())
+        }
+
+        /// Set the time after a verifying key upgrade while the previous key is accepted
+        ///
+        /// Can be called only by the owner. The period is applied to the next upgrade,
+        /// the previous key of the last upgrade is accepted until the same time
+        #[ink(message)]
+        pub fn set_key_grace_period(&mut self, period: Timestamp) -> Result<()> {
+            self.ensure_owner()?;
+
+            self.key_grace_period = period;
+
+            Ok(())
+        }
+
+        /// Returns the hash of the current verifying key
+        #[ink(message)]
+        pub fn verifying_key_hash(&self) -> KeyHash {
+            self.verifying_key_hash
+        }
+
+        /// Returns the verifying key with provided hash
+        #[ink(message)]
+        pub fn verifying_key(&self, key_hash: KeyHash) -> Option<VerifyingKey> {
+            self.verifying_keys.get(key_hash)
+        }
+
+        /// Returns the hash of the proposed verifying key and the time when it can be executed
+        #[ink(message)]
+        pub fn proposed_verifying_key(&self) -> Option<(KeyHash, Timestamp)> {
+            self.proposed_key_hash()
+                .ok()
+                .map(|key_hash| (key_hash, self.proposed_key_executable_at))
+        }
+
+        /// Returns the hash of the previous verifying key and the time until it is accepted
+        #[ink(message)]
+        pub fn previous_verifying_key(&self) -> Option<(KeyHash, Timestamp)> {
+            if self.previous_key_hash == KeyHash::default() {
+                return None;
+            }
+
+            Some((self.previous_key_hash, self.previous_key_valid_until))
+        }
+
+        /// Returns the time between proposing and executing a verifying key
+        #[ink(message)]
+        pub fn key_upgrade_delay(&self) -> Timestamp {
+            self.key_upgrade_delay
+        }
+
+        /// Returns the time after a verifying key upgrade while the previous key is accepted
+        #[ink(message)]
+        pub fn key_grace_period(&self) -> Timestamp {
+            self.key_grace_period
+        }
+
+        /// Returns the owner of the contract
+        #[ink(message)]
+        pub fn owner(&self) -> AccountId {
+            self.owner
+        }
+
+        /// Returns whether deposits are paused
+        #[ink(message)]
+        pub fn deposits_paused(&self) -> bool {
+            self.deposits_paused
+        }
+
+        /// Returns whether withdrawals are paused
+        #[ink(message)]
+        pub fn withdrawals_paused(&self) -> bool {
+            self.withdrawals_paused
+        }
+
+        /// Returns the identifier of the chain which the contract is instantiated with
+        #[ink(message)]
+        pub fn chain_id(&self) -> ChainId {
+            self.chain_id
+        }
+
+        /// Returns the domain of the withdrawal proofs,
+        /// the hash of the contract address and the chain identifier
+        #[ink(message)]
+        pub fn domain(&self) -> PoseidonHash {
+            self.domain
+        }
+
+        /// Returns the merkle tree parameters of the pools
+        #[ink(message)]
+        pub fn tree_config(&self) -> TreeConfig {
+            self.tree_config
+        }
+
+        /// Returns the denominations of all pools
+        #[ink(message)]
+        pub fn denominations(&self) -> Vec<Balance> {
+            self.denominations.clone()
+        }
+
+        /// Returns the current epoch of the pool, which new deposits are inserted into
+        #[ink(message)]
+        pub fn current_epoch(&self, denomination: Balance) -> Result<EpochId> {
+            self.pool_tree(denomination)?;
+
+            Ok(self.epochs.get(denomination).unwrap_or_default())
+        }
+
+        /// Returns the leaf index for the next deposit into the pool in the current epoch
+        ///
+        /// Tree of the current epoch is replaced with an empty one on the next deposit
+        /// when `next_index` reaches `2^depth`
+        #[ink(message)]
+        pub fn next_index(&self, denomination: Balance) -> Result<u64> {
+            Ok(self.pool_tree(denomination)?.next_index)
+        }
+
+        /// Returns the known merkle_tree root hashes of the pool in the current epoch,
+        /// from the oldest to the last one
+        #[ink(message)]
+        pub fn get_root_history(&self, denomination: Balance) -> Result<Vec<PoseidonHash>> {
+            Ok(self.pool_tree(denomination)?.get_roots())
+        }
+
+        /// Returns the merkle_tree root hashes of the pool in the `epoch`,
+        /// from the oldest to the last one
+        ///
+        /// For the earlier epochs, these are the final roots of the full tree
+        #[ink(message)]
+        pub fn get_epoch_root_history(
+            &self,
+            denomination: Balance,
+            epoch: EpochId,
+        ) -> Result<Vec<PoseidonHash>> {
+            let current_epoch = self.current_epoch(denomination)?;
+
+            if epoch == current_epoch {
+                return self.get_root_history(denomination);
+            }
+
+            self.archived_root_histories
+                .get((denomination, epoch))
+                .ok_or(Error::UnknownEpoch)
+        }
+
+        /// Checks that the root hash is in the merkle_tree root history of the pool in any epoch
+        #[ink(message)]
+        pub fn is_known_root(&self, denomination: Balance, root: PoseidonHash) -> bool {
+            self.root_epoch(denomination, root).is_some()
+        }
+
+        /// Returns the epoch of the root hash if it is in the merkle_tree
+        /// root history of the pool
+        #[ink(message)]
+        pub fn root_epoch(&self, denomination: Balance, root: PoseidonHash) -> Option<EpochId> {
+            let merkle_tree = self.pool_tree(denomination).ok()?;
+
+            self.known_root_epoch(denomination, &merkle_tree, root)
+        }
+
+        /// Checks that the nullifier hash has been already used for withdraw from the pool
+        #[ink(message)]
+        pub fn is_spent(&self, denomination: Balance, nullifier_hash: PoseidonHash) -> bool {
+            self.used_nullifiers
+                .get((denomination, nullifier_hash))
+                .is_some()
+        }
+
+        /// Checks every nullifier hash from the list, see `is_spent`
+        #[ink(message)]
+        pub fn are_spent(
+            &self,
+            denomination: Balance,
+            nullifier_hashes: Vec<PoseidonHash>,
+        ) -> Vec<bool> {
+            nullifier_hashes
+                .into_iter()
+                .map(|nullifier_hash| self.is_spent(denomination, nullifier_hash))
+                .collect()
+        }
+
+        /// Check that the verifying key can be deserialized and its verifier data
+        /// is compiled for the tree depth, so an accepted key always gives a verifier
+        fn check_verifying_key(&self, key: &VerifyingKey) -> Result<()> {
+            if key.opening_key.len() != SERIALIZED_OPENING_KEY.len() {
+                return Err(Error::InvalidVerifyingKey);
+            }
+
+            let depth = create_verifier(key).and_then(|verifier| verifier.depth());
+            if depth != Some(self.tree_config.depth as usize) {
+                return Err(Error::InvalidVerifyingKey);
+            }
+
+            Ok(())
+        }
+
+        /// Save the verifying key and return its hash
+        fn store_verifying_key(&mut self, key: &VerifyingKey) -> KeyHash {
+            let key_hash = self.env().hash_encoded::<ink_env::hash::Blake2x256, _>(key);
+            self.verifying_keys.insert(key_hash, key);
+
+            key_hash
+        }
+
+        /// Remove the verifying key from storage, unless it is still the current,
+        /// the previous or the proposed key
+        fn remove_verifying_key(&mut self, key_hash: KeyHash) {
+            if key_hash != self.verifying_key_hash
+                && key_hash != self.previous_key_hash
+                && key_hash != self.proposed_key_hash
+            {
+                self.verifying_keys.remove(key_hash);
+            }
+        }
+
+        /// Get hash of the proposed verifying key
+        fn proposed_key_hash(&self) -> Result<KeyHash> {
+            if self.proposed_key_hash == KeyHash::default() {
+                return Err(Error::NoKeyProposal);
+            }
+
+            Ok(self.proposed_key_hash)
+        }
+
+        /// Verifiers for the current verifying key,
+        /// and for the previous one during the grace period
+        fn accepted_verifiers(&self) -> Vec<SlushieVerifier> {
+            let mut key_hashes = Vec::from([self.verifying_key_hash]);
+
+            if self.previous_key_hash != KeyHash::default()
+                && self.env().block_timestamp() < self.previous_key_valid_until
+            {
+                key_hashes.push(self.previous_key_hash);
+            }
+
+            key_hashes
+                .into_iter()
+                .filter_map(|key_hash| self.verifying_keys.get(key_hash))
+                .filter_map(|key| create_verifier(&key))
+                .collect()
+        }
+
+        /// Get the epoch of the root hash from the current merkle tree
+        /// or from the archived root histories
+        fn known_root_epoch(
+            &self,
+            denomination: Balance,
+            merkle_tree: &PoolTree,
+            root: PoseidonHash,
+        ) -> Option<EpochId> {
+            if merkle_tree.is_known_root(root) {
+                return Some(self.epochs.get(denomination).unwrap_or_default());
+            }
+
+            self.archived_roots.get((denomination, root))
+        }
+
+        /// Check that the withdrawal can be paid, nothing is changed
+        ///
+        /// Returns the epoch of the root
+        fn validate_withdrawal(
+            &self,
+            denomination: Balance,
+            merkle_tree: &PoolTree,
+            verifiers: &[SlushieVerifier],
+            relayer: AccountId,
+            public_inputs: &PublicInputs,
+        ) -> Result<EpochId> {
+            let epoch = self.check_withdrawal(denomination, merkle_tree, public_inputs)?;
+
+            // Check provided proof, which is bound to the pool denomination and the contract domain
+            // and accepted by any of the verifiers
+            if !verifiers.iter().any(|verifier| {
+                check_proof(verifier, public_inputs, relayer, denomination, self.domain)
+            }) {
+                return Err(Error::VerificationProofFailed);
+            }
+
+            Ok(epoch)
+        }
+
+        /// Check the withdrawal against the pool state, the proof is not checked
+        ///
+        /// Returns the epoch of the root
+        fn check_withdrawal(
+            &self,
+            denomination: Balance,
+            merkle_tree: &PoolTree,
+            public_inputs: &PublicInputs,
+        ) -> Result<EpochId> {
+            // Check that provided root is known in any epoch
+            let epoch = self
+                .known_root_epoch(denomination, merkle_tree, public_inputs.root)
+                .ok_or(Error::UnknownRoot)?;
+
+            // Check that contract has enough balance
+            if self.balance()? < denomination {
+                return Err(Error::InsufficientFunds);
+            }
+
+            // Check that relayer fee can be covered by the deposit
+            if public_inputs.fee as Balance > denomination {
+                return Err(Error::FeeExceedsDepositSize);
+            }
+
+            // Check that provided nullifier hash is not used
+            if self.is_spent(denomination, public_inputs.nullifier_hash) {
+                return Err(Error::NullifierAlreadyUsed);
+            }
+
+            Ok(epoch)
+        }
+
+        /// Pay the validated withdrawal, mark its nullifier hash as used and emit Withdrawn event
+        fn complete_withdrawal(
+            &mut self,
+            denomination: Balance,
+            epoch: EpochId,
+            relayer: AccountId,
+            public_inputs: &PublicInputs,
+        ) -> Result<()> {
+            let fee = public_inputs.fee as Balance;
+
+            // Transfer to recipient and relayer
+            self.pay_withdrawal(denomination, public_inputs.recipient, relayer, fee)?;
+
+            // Save used nullifier hash
+            self.used_nullifiers
+                .insert((denomination, public_inputs.nullifier_hash), &true);
+
+            // Emit Withdrawn Event
+            self.env().emit_event(Withdrawn {
+                denomination,
+                epoch,
+                hash: public_inputs.nullifier_hash,
+                recipient: public_inputs.recipient,
+                relayer,
+                fee,
+                timestamp: self.env().block_timestamp(),
+            });
+
+            Ok(())
+        }
+
+        /// Check that the caller is the owner
+        fn ensure_owner(&self) -> Result<()> {
+            if self.env().caller() != self.owner {
+                return Err(Error::NotOwner);
+            }
+
+            Ok(())
+        }
+
+        /// Check that deposits are not paused
+        fn ensure_deposits_not_paused(&self) -> Result<()> {
+            if self.deposits_paused {
+                return Err(Error::DepositsPaused);
+            }
+
+            Ok(())
+        }
+
+        fn emit_pause_changed(&self) {
+            self.env().emit_event(PauseChanged {
+                deposits_paused: self.deposits_paused,
+                withdrawals_paused: self.withdrawals_paused,
+            });
+        }
+
+        /// Get merkle tree of the pool with provided denomination
+        fn pool_tree(&self, denomination: Balance) -> Result<PoolTree> {
+            self.merkle_trees
+                .get(denomination)
+                .ok_or(Error::UnknownPool)
+        }
+
+        /// Get merkle tree of the pool in the current epoch to insert commitments into
+        fn pending_tree(&self, denomination: Balance) -> Result<PendingTree> {
+            let merkle_tree = self.pool_tree(denomination)?;
+
+            Ok(PendingTree {
+                epoch: self.epochs.get(denomination).unwrap_or_default(),
+                merkle_tree,
+                archived: Vec::new(),
+            })
+        }
+
+        /// Transfer `denomination - fee` to the recipient and `fee` to the relayer
+        ///
+        /// For the PSP34 pool, a token from the pool inventory is released
+        /// to the recipient before.
+        /// Transfers are atomic: if a transfer fails after the recipient
+        /// has been paid, the contract traps so that the whole call is reverted
+        fn pay_withdrawal(
+            &mut self,
+            denomination: Balance,
+            recipient: AccountId,
+            relayer: AccountId,
+            fee: Balance,
+        ) -> Result<()> {
+            let mut paid = false;
+
+            if let Asset::Psp34(collection) = self.asset {
+                self.release_nft(collection, denomination, recipient)?;
+                paid = true;
+            }
+
+            let amount = denomination - fee;
+
+            if amount > 0 {
+                if let Err(err) = self.transfer(recipient, amount) {
+                    if paid {
+                        panic!("recipient transfer failed after releasing the token");
+                    }
+
+                    return Err(err);
+                }
+                paid = true;
+            }
+
+            if fee > 0 {
+                if let Err(err) = self.transfer(relayer, fee) {
+                    if paid {
+                        panic!("relayer fee transfer failed after paying the recipient");
+                    }
+
+                    return Err(err);
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Check that the pool can pay `count` withdrawals: the contract holds enough
+        /// of the asset and, for the PSP34 pool, enough tokens in the pool inventory
+        fn ensure_can_pay(&self, denomination: Balance, count: usize) -> Result<()> {
+            if self.balance()? < denomination.saturating_mul(count as Balance) {
+                return Err(Error::InsufficientFunds);
+            }
+
+            if let Asset::Psp34(_) = self.asset {
+                let inventory = self.nft_inventory.get(denomination).unwrap_or_default();
+                if inventory.len() < count {
+                    return Err(Error::InsufficientFunds);
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Transfer a token from the pool inventory to the recipient
+        fn release_nft(
+            &mut self,
+            collection: AccountId,
+            denomination: Balance,
+            recipient: AccountId,
+        ) -> Result<()> {
+            let mut inventory = self.nft_inventory.get(denomination).unwrap_or_default();
+            let id = inventory.pop().ok_or(Error::InsufficientFunds)?;
+
+            psp34::transfer(collection, recipient, id)?;
+            self.nft_inventory.insert(denomination, &inventory);
+
+            Ok(())
+        }
+
+        /// Transfer `value` of the pools asset from the contract
+        ///
+        /// The PSP34 pool pays in the native balance
+        fn transfer(&self, to: AccountId, value: Balance) -> Result<()> {
+            match self.asset {
+                Asset::Native | Asset::Psp34(_) => self
+                    .env()
+                    .transfer(to, value)
+                    .map_err(|_| Error::TransferFailed),
+                Asset::Psp22(token) => Ok(psp22::transfer(token, to, value)?),
+            }
+        }
+
+        /// Balance of the pools asset which the contract holds
+        fn balance(&self) -> Result<Balance> {
+            match self.asset {
+                Asset::Native | Asset::Psp34(_) => Ok(self.env().balance()),
+                Asset::Psp22(token) => Ok(psp22::balance_of(token, self.env().account_id())?),
+            }
+        }
+    }
+
+    /// Unit tests
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::tree::merkle_tree::MAX_ROOT_HISTORY_SIZE;
+        use hex_literal::hex;
+        use shared::constants::MAX_DEPTH;
+        use shared::functions::{add_modulus, scalar_to_bytes, u128_to_scalar};
+
+        const SERIALIZED_PUBLIC_PARAMETERS: &[u8] = include_bytes!("../public-parameters/pp-test");
+
+        /// Chain identifier which the contracts are instantiated with in tests
+        const CHAIN_ID: ChainId = [7; 32];
+
+        /// Imports `ink_lang` so we can use `#[ink::test]`.
+        use ink_lang as ink;
+        use plonk_prover::public_parameters_generation::{
+            generate_test_public_parameters, generate_verifier_data,
+            generate_verifier_data_with_depth,
+        };
+        use plonk_prover::{prove, GeneratedCommitment};
+
+        /// Advance blocks until the block timestamp reaches `timestamp`
+        fn advance_time_to(timestamp: Timestamp) {
+            while ink_env::block_timestamp::<ink_env::DefaultEnvironment>() < timestamp {
+                ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            }
+        }
+
+        /// Well-formed verifying key which can't verify any proof, as it is compiled
+        /// with another public parameters
+        fn broken_verifying_key() -> VerifyingKey {
+            let public_parameters = generate_test_public_parameters().unwrap();
+            let (verifier_data, opening_key) = generate_verifier_data(&public_parameters).unwrap();
+
+            VerifyingKey {
+                verifier_data,
+                opening_key: opening_key.to_vec(),
+            }
+        }
+
+        /// Deposit commitments to the pool and generate withdrawal public inputs for them,
+        /// relayer is the recipient and the fee is zero
+        fn deposit_and_prove(
+            slushie: &mut Slushie,
+            denomination: Balance,
+            recipient: AccountId,
+            count: usize,
+        ) -> Vec<PublicInputs> {
+            let commitments: Vec<GeneratedCommitment> = (0..count)
+                .map(|_| plonk_prover::generate_commitment())
+                .collect();
+            let commitment_bytes: Vec<PoseidonHash> = commitments
+                .iter()
+                .map(|commitment| commitment.commitment_bytes)
+                .collect();
+
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(
+                denomination * count as Balance,
+            );
+            let root = slushie
+                .deposit_batch(denomination, commitment_bytes.clone())
+                .unwrap();
+
+            let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> =
+                (&commitment_bytes[..]).try_into().unwrap();
+
+            commitments
+                .iter()
+                .enumerate()
+                .map(|(i, commitment)| PublicInputs {
+                    nullifier_hash: commitment.nullifier_hash_bytes,
+                    root,
+                    proof: prove(
+                        SERIALIZED_PUBLIC_PARAMETERS,
+                        i,
+                        root,
+                        tree.opening(i).unwrap(),
+                        commitment.nullifier,
+                        commitment.randomness,
+                        *recipient.as_ref(),
+                        *recipient.as_ref(),
+                        0,
+                        denomination,
+                        slushie.domain(),
+                    )
+                    .unwrap(),
+                    fee: 0,
+                    recipient,
+                })
+                .collect()
+        }
+
+        /// Deposit a new commitment to every pool, so all pools have the same root,
+        /// and generate public inputs for its withdrawal from the pool to the recipient
+        /// with a proof for the domain, the fee is zero
+        fn deposit_to_all_pools_and_prove(
+            slushie: &mut Slushie,
+            denomination: Balance,
+            recipient: AccountId,
+            domain: PoseidonHash,
+        ) -> PublicInputs {
+            let GeneratedCommitment {
+                nullifier,
+                randomness,
+                commitment_bytes,
+                nullifier_hash_bytes,
+            } = plonk_prover::generate_commitment();
+
+            let mut root = [0; 32];
+            for pool in slushie.denominations.clone() {
+                ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(pool);
+                root = slushie.deposit(pool, commitment_bytes).unwrap();
+            }
+
+            let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> =
+                (&[commitment_bytes][..]).try_into().unwrap();
+
+            PublicInputs {
+                nullifier_hash: nullifier_hash_bytes,
+                root,
+                proof: prove(
+                    SERIALIZED_PUBLIC_PARAMETERS,
+                    0,
+                    root,
+                    tree.opening(0).unwrap(),
+                    nullifier,
+                    randomness,
+                    *recipient.as_ref(),
+                    *recipient.as_ref(),
+                    0,
+                    denomination,
+                    domain,
+                )
+                .unwrap(),
+                fee: 0,
+                recipient,
+            }
+        }
+
+        /// Events of the contract
+        type Event = <Slushie as ::ink_lang::reflect::ContractEventBase>::Type;
+
+        /// Decode event emitted by the contract
+        fn decode_event(event: &ink_env::test::EmittedEvent) -> Event {
+            <Event as scale::Decode>::decode(&mut &event.data[..])
+                .expect("encountered invalid contract event data buffer")
+        }
+
+        #[ink::test]
+        fn test_constructor() {
+            let slushie: Slushie = Slushie::new(vec![13, 100], CHAIN_ID);
+
+            assert_eq!(slushie.denominations, vec![13, 100]);
+            let empty_tree = || PoolTree::new(DEFAULT_DEPTH, DEFAULT_ROOT_HISTORY_SIZE).unwrap();
+            assert_eq!(slushie.tree_config(), TreeConfig::default());
+            assert_eq!(slushie.pool_tree(13), Ok(empty_tree()));
+            assert_eq!(slushie.pool_tree(100), Ok(empty_tree()));
+            assert_eq!(slushie.pool_tree(55), Err(Error::UnknownPool));
+        }
+
+        /// - proofs are bound to the contract address and the chain identifier
+        #[ink::test]
+        fn constructor_sets_domain() {
+            let slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+            let callee = ink_env::test::callee::<ink_env::DefaultEnvironment>();
+
+            assert_eq!(slushie.chain_id(), CHAIN_ID);
+            assert_eq!(
+                slushie.domain(),
+                withdrawal_domain(*callee.as_ref(), CHAIN_ID)
+            );
+            assert_ne!(
+                slushie.domain(),
+                withdrawal_domain(*callee.as_ref(), [8; 32])
+            );
+        }
+
+        /// - domain hashes the 128-bit halves of the contract account and the chain identifier,
+        ///   which the relayer and the CLI compute from the SS58 address and the genesis hash
+        #[ink::test]
+        fn domain_matches_relayer_encoding() {
+            let slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+            let callee = ink_env::test::callee::<ink_env::DefaultEnvironment>();
+
+            let halves = |bytes: [u8; 32]| {
+                let (high, low) = bytes.split_at(16);
+                [
+                    u128_to_scalar(u128::from_be_bytes(high.try_into().unwrap())),
+                    u128_to_scalar(u128::from_be_bytes(low.try_into().unwrap())),
+                ]
+            };
+            let [contract_high, contract_low] = halves(*callee.as_ref());
+            let [chain_id_high, chain_id_low] = halves(CHAIN_ID);
+
+            assert_eq!(
+                slushie.domain(),
+                scalar_to_bytes(dusk_poseidon::sponge::hash(&[
+                    contract_high,
+                    contract_low,
+                    chain_id_high,
+                    chain_id_low,
+                ]))
+            );
+        }
+
+        /// - pools use merkle trees with the depth and root history size from the constructor
+        #[ink::test]
+        fn constructor_with_tree_config_works() {
+            let tree_config = TreeConfig {
+                depth: 2,
+                root_history_size: 3,
+            };
+            let (verifier_data, opening_key) =
+                generate_verifier_data_with_depth::<2>(SERIALIZED_PUBLIC_PARAMETERS).unwrap();
+            let verifying_key = VerifyingKey {
+                verifier_data,
+                opening_key: opening_key.to_vec(),
+            };
+            let mut slushie: Slushie = Slushie::new_with_tree_config(
+                Asset::Native,
+                vec![13],
+                tree_config,
+                Some(verifying_key.clone()),
+                None,
+                CHAIN_ID,
+            );
+
+            assert_eq!(slushie.tree_config(), tree_config);
+            assert_eq!(
+                slushie.verifying_key(slushie.verifying_key_hash()),
+                Some(verifying_key)
+            );
+
+            for i in 0..5 {
+                ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+                slushie.deposit(13, [i + 1; 32]).unwrap();
+            }
+
+            // Tree of depth 2 is full after 4 deposits, the fifth one starts the next epoch
+            assert_eq!(slushie.current_epoch(13).unwrap(), 1);
+            assert_eq!(slushie.next_index(13).unwrap(), 1);
+            assert_eq!(slushie.get_root_history(13).unwrap().len(), 2);
+            assert_eq!(slushie.get_epoch_root_history(13, 0).unwrap().len(), 3);
+
+            // Bundled key is compiled for the default depth only
+            assert_eq!(
+                slushie
+                    .propose_verifying_key(bundled_verifying_key(DEFAULT_DEPTH as u8).unwrap())
+                    .unwrap_err(),
+                Error::InvalidVerifyingKey
+            );
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "DepthIsZero")]
+        fn constructor_with_zero_depth_fails() {
+            let tree_config = TreeConfig {
+                depth: 0,
+                root_history_size: 3,
+            };
+            let _slushie: Slushie = Slushie::new_with_tree_config(
+                Asset::Native,
+                vec![13],
+                tree_config,
+                None,
+                None,
+                CHAIN_ID,
+            );
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "DepthTooLong")]
+        fn constructor_with_too_long_depth_fails() {
+            let tree_config = TreeConfig {
+                depth: MAX_DEPTH as u8 + 1,
+                root_history_size: 3,
+            };
+            let _slushie: Slushie = Slushie::new_with_tree_config(
+                Asset::Native,
+                vec![13],
+                tree_config,
+                None,
+                None,
+                CHAIN_ID,
+            );
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "RootHistoryIsEmpty")]
+        fn constructor_with_empty_root_history_fails() {
+            let tree_config = TreeConfig {
+                depth: DEFAULT_DEPTH as u8,
+                root_history_size: 0,
+            };
+            let _slushie: Slushie = Slushie::new_with_tree_config(
+                Asset::Native,
+                vec![13],
+                tree_config,
+                None,
+                None,
+                CHAIN_ID,
+            );
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "RootHistoryTooLong")]
+        fn constructor_with_too_long_root_history_fails() {
+            let tree_config = TreeConfig {
+                depth: DEFAULT_DEPTH as u8,
+                root_history_size: MAX_ROOT_HISTORY_SIZE as u32 + 1,
+            };
+            let _slushie: Slushie = Slushie::new_with_tree_config(
+                Asset::Native,
+                vec![13],
+                tree_config,
+                None,
+                None,
+                CHAIN_ID,
+            );
+        }
+
+        #[ink::test]
+        fn constructor_with_max_root_history_works() {
+            let tree_config = TreeConfig {
+                depth: DEFAULT_DEPTH as u8,
+                root_history_size: MAX_ROOT_HISTORY_SIZE as u32,
+            };
+            let slushie: Slushie = Slushie::new_with_tree_config(
+                Asset::Native,
+                vec![13],
+                tree_config,
+                None,
+                None,
+                CHAIN_ID,
+            );
+
+            assert_eq!(slushie.tree_config(), tree_config);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "verifying key should be provided for the tree depth")]
+        fn constructor_without_key_for_tree_depth_fails() {
+            let tree_config = TreeConfig {
+                depth: 4,
+                root_history_size: 3,
+            };
+            let _slushie: Slushie = Slushie::new_with_tree_config(
+                Asset::Native,
+                vec![13],
+                tree_config,
+                None,
+                None,
+                CHAIN_ID,
+            );
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "denominations should be unique")]
+        fn constructor_with_duplicated_denominations_fails() {
+            let _slushie: Slushie = Slushie::new(vec![13, 100, 13], CHAIN_ID);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "denomination should be positive")]
+        fn constructor_with_zero_denomination_fails() {
+            let _slushie: Slushie = Slushie::new(vec![0], CHAIN_ID);
+        }
+
+        /// can deposit funds with a proper `deposit_size`
+        #[ink::test]
+        fn deposit_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+            let commitment: PoseidonHash =
+                hex!("0001020304050607 08090a0b0c0d0e0f 0001020304050607 08090a0b0c0d0e0f");
+
+            let initial_root_hash = slushie.get_root_hash(13).unwrap();
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+            let res = slushie.deposit(13, commitment);
+            assert!(res.is_ok());
+
+            let resulting_root_hash = slushie.get_root_hash(13).unwrap();
+            assert_ne!(initial_root_hash, resulting_root_hash);
+        }
+
+        /// query messages show deposit size, next index and root history
+        #[ink::test]
+        fn queries_after_deposits_work() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+
+            assert_eq!(slushie.denominations(), vec![13]);
+            assert_eq!(slushie.next_index(13).unwrap(), 0);
+            assert_eq!(
+                slushie.get_root_history(13).unwrap(),
+                vec![slushie.get_root_hash(13).unwrap()]
+            );
+
+            let initial_root_hash = slushie.get_root_hash(13).unwrap();
+            let mut roots = vec![initial_root_hash];
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            for i in 0..3 {
+                ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+                let root = slushie.deposit(13, [i + 1; 32]).unwrap();
+                roots.push(root);
+
+                assert_eq!(slushie.next_index(13).unwrap(), i as u64 + 1);
+            }
+
+            assert_eq!(slushie.get_root_history(13).unwrap(), roots);
+            assert!(slushie.is_known_root(13, initial_root_hash));
+            assert!(slushie.is_known_root(13, slushie.get_root_hash(13).unwrap()));
+            assert!(!slushie.is_known_root(13, [7; 32]));
+        }
+
+        /// deposit emits event with the leaf index and the new root
+        #[ink::test]
+        fn deposit_emits_event() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            let mut roots = Vec::new();
+            for i in 0..2 {
+                ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+                roots.push(slushie.deposit(13, [i + 1; 32]).unwrap());
+            }
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 2);
+
+            for (i, event) in emitted_events.iter().enumerate() {
+                assert_eq!(event.topics.len(), 3);
+
+                if let Event::Deposited(Deposited {
+                    denomination,
+                    epoch,
+                    hash,
+                    leaf_index,
+                    root,
+                    ..
+                }) = decode_event(event)
+                {
+                    assert_eq!(denomination, 13);
+                    assert_eq!(epoch, 0);
+                    assert_eq!(hash, [i as u8 + 1; 32]);
+                    assert_eq!(leaf_index, i as u64);
+                    assert_eq!(root, roots[i]);
+                } else {
+                    panic!("Deposited event is expected");
+                }
+            }
+        }
+
+        /// can't deposit funds with an invalid `deposit_size`
+        #[ink::test]
+        fn deposit_invalid_amount_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let deposit_size = 13;
+            let invalid_deposit_size = 55;
+            let mut slushie: Slushie = Slushie::new(vec![deposit_size], CHAIN_ID);
+            let commitment: PoseidonHash =
+                hex!("0001020304050607 08090a0b0c0d0e0f 0001020304050607 08090a0b0c0d0e0f");
+
+            let initial_root_hash = slushie.get_root_hash(deposit_size).unwrap();
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(
+                invalid_deposit_size,
+            );
+            let res = slushie.deposit(deposit_size, commitment);
+            assert_eq!(res.unwrap_err(), Error::InvalidTransferredAmount);
+
+            let resulting_root_hash = slushie.get_root_hash(deposit_size).unwrap();
+            assert_eq!(initial_root_hash, resulting_root_hash);
+        }
+
+        /// can't deposit funds if account doesn't have enough money
+        ///
+        /// this case shouldn't be tested cause is a pallete, which
+        /// checks the sufficient amount of funds
+
+        /// - can withdraw funds with a proper deposit_size and hash
+        #[ink::test]
+        fn withdraw_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let deposit_size: Balance = 13;
+            let mut slushie: Slushie = Slushie::new(vec![deposit_size], CHAIN_ID);
+
+            let GeneratedCommitment {
+                nullifier,
+                randomness,
+                commitment_bytes,
+                nullifier_hash_bytes,
+            } = plonk_prover::generate_commitment();
+
+            let hash: PoseidonHash = commitment_bytes;
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(deposit_size);
+            let res = slushie.deposit(deposit_size, hash);
+            assert!(res.is_ok());
+
+            let resulting_root_hash = slushie.get_root_hash(deposit_size).unwrap();
+
+            let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> =
+                (&[commitment_bytes][..]).try_into().unwrap();
+
+            let tree_opening = tree.opening(0).unwrap();
+
+            let proof = prove(
+                SERIALIZED_PUBLIC_PARAMETERS,
+                0,
+                resulting_root_hash,
+                tree_opening,
+                nullifier,
+                randomness,
+                *accounts.alice.as_ref(),
+                *accounts.alice.as_ref(),
+                3,
+                deposit_size,
+                slushie.domain(),
+            )
+            .unwrap();
+
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(deposit_size);
+            let res = slushie.withdraw(
+                deposit_size,
+                PublicInputs {
+                    nullifier_hash: nullifier_hash_bytes,
+                    root: resulting_root_hash,
+                    proof,
+                    fee: 3,
+                    recipient: accounts.alice,
+                },
+            );
+            assert!(res.is_ok());
+        }
+
+        /// - can withdraw funds with a proper deposit_size and hash by different account
+        #[ink::test]
+        fn withdraw_from_different_account_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let deposit_size = 13;
+            let mut slushie: Slushie = Slushie::new(vec![deposit_size], CHAIN_ID);
+
+            let GeneratedCommitment {
+                nullifier,
+                randomness,
+                commitment_bytes,
+                nullifier_hash_bytes,
+            } = plonk_prover::generate_commitment();
+
+            let hash: PoseidonHash = commitment_bytes;
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(deposit_size);
+            let res = slushie.deposit(deposit_size, hash);
+            assert!(res.is_ok());
+
+            let resulting_root_hash = slushie.get_root_hash(deposit_size).unwrap();
+
+            let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> =
+                (&[commitment_bytes][..]).try_into().unwrap();
+
+            let tree_opening = tree.opening(0).unwrap();
+
+            let proof = prove(
+                SERIALIZED_PUBLIC_PARAMETERS,
+                0,
+                resulting_root_hash,
+                tree_opening,
+                nullifier,
+                randomness,
+                *accounts.eve.as_ref(),
+                *accounts.eve.as_ref(),
+                0,
+                deposit_size,
+                slushie.domain(),
+            )
+            .unwrap();
+
+            ink_env::test::set_caller::<Environment>(accounts.eve);
+            let res = slushie.withdraw(
+                deposit_size,
+                PublicInputs {
+                    nullifier_hash: nullifier_hash_bytes,
+                    root: resulting_root_hash,
+                    proof,
+                    fee: 0,
+                    recipient: accounts.eve,
+                },
+            );
+            assert!(res.is_ok());
+        }
+
+        /// - withdrawal through a relayer pays `deposit_size - fee` to recipient and `fee` to relayer
+        #[ink::test]
+        fn withdraw_through_relayer_pays_fee() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let deposit_size: Balance = 13;
+            let fee: u64 = 5;
+            let mut slushie: Slushie = Slushie::new(vec![deposit_size], CHAIN_ID);
+
+            let GeneratedCommitment {
+                nullifier,
+                randomness,
+                commitment_bytes,
+                nullifier_hash_bytes,
+            } = plonk_prover::generate_commitment();
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(deposit_size);
+            let res = slushie.deposit(deposit_size, commitment_bytes);
+            assert!(res.is_ok());
+
+            let resulting_root_hash = slushie.get_root_hash(deposit_size).unwrap();
+
+            let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> =
+                (&[commitment_bytes][..]).try_into().unwrap();
+
+            let tree_opening = tree.opening(0).unwrap();
+
+            // Charlie is the recipient, Bob relays the withdrawal
+            let proof = prove(
+                SERIALIZED_PUBLIC_PARAMETERS,
+                0,
+                resulting_root_hash,
+                tree_opening,
+                nullifier,
+                randomness,
+                *accounts.charlie.as_ref(),
+                *accounts.bob.as_ref(),
+                fee,
+                deposit_size,
+                slushie.domain(),
+            )
+            .unwrap();
+
+            let contract = ink_env::test::callee::<ink_env::DefaultEnvironment>();
+            let contract_balance =
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(contract)
+                    .unwrap();
+            let recipient_balance =
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.charlie)
+                    .unwrap();
+            let relayer_balance =
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.bob)
+                    .unwrap();
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            let res = slushie.withdraw(
+                deposit_size,
+                PublicInputs {
+                    nullifier_hash: nullifier_hash_bytes,
+                    root: resulting_root_hash,
+                    proof,
+                    fee,
+                    recipient: accounts.charlie,
+                },
+            );
+            assert!(res.is_ok());
+
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(contract)
+                    .unwrap(),
+                contract_balance - deposit_size
+            );
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.charlie)
+                    .unwrap(),
+                recipient_balance + deposit_size - fee as Balance
+            );
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.bob)
+                    .unwrap(),
+                relayer_balance + fee as Balance
+            );
+
+            // Withdrawn event is the last one and contains the payout details
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let withdrawn_event = emitted_events.last().unwrap();
+            assert_eq!(withdrawn_event.topics.len(), 4);
+
+            if let Event::Withdrawn(Withdrawn {
+                hash,
+                recipient,
+                relayer,
+                fee: paid_fee,
+                ..
+            }) = decode_event(withdrawn_event)
+            {
+                assert_eq!(hash, nullifier_hash_bytes);
+                assert_eq!(recipient, accounts.charlie);
+                assert_eq!(relayer, accounts.bob);
+                assert_eq!(paid_fee, fee as Balance);
+            } else {
+                panic!("Withdrawn event is expected");
+            }
+        }
+
+        /// - relayer can take the whole deposit as a fee
+        #[ink::test]
+        fn withdraw_with_fee_equal_to_deposit_size_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let deposit_size: Balance = 13;
+            let mut slushie: Slushie = Slushie::new(vec![deposit_size], CHAIN_ID);
+
+            let GeneratedCommitment {
+                nullifier,
+                randomness,
+                commitment_bytes,
+                nullifier_hash_bytes,
+            } = plonk_prover::generate_commitment();
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(deposit_size);
+            let res = slushie.deposit(deposit_size, commitment_bytes);
+            assert!(res.is_ok());
+
+            let resulting_root_hash = slushie.get_root_hash(deposit_size).unwrap();
+
+            let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> =
+                (&[commitment_bytes][..]).try_into().unwrap();
+
+            let tree_opening = tree.opening(0).unwrap();
+
+            let proof = prove(
+                SERIALIZED_PUBLIC_PARAMETERS,
+                0,
+                resulting_root_hash,
+                tree_opening,
+                nullifier,
+                randomness,
+                *accounts.charlie.as_ref(),
+                *accounts.bob.as_ref(),
+                deposit_size as u64,
+                deposit_size,
+                slushie.domain(),
+            )
+            .unwrap();
+
+            let recipient_balance =
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.charlie)
+                    .unwrap();
+            let relayer_balance =
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.bob)
+                    .unwrap();
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            let res = slushie.withdraw(
+                deposit_size,
+                PublicInputs {
+                    nullifier_hash: nullifier_hash_bytes,
+                    root: resulting_root_hash,
+                    proof,
+                    fee: deposit_size as u64,
+                    recipient: accounts.charlie,
+                },
+            );
+            assert!(res.is_ok());
+
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.charlie)
+                    .unwrap(),
+                recipient_balance
+            );
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.bob)
+                    .unwrap(),
+                relayer_balance + deposit_size
+            );
+        }
+
+        /// - can't withdraw funds with a fee bigger than deposit_size
+        #[ink::test]
+        fn withdraw_with_fee_exceeding_deposit_size_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let deposit_size = 13;
+            let mut slushie: Slushie = Slushie::new(vec![deposit_size], CHAIN_ID);
+            let hash: PoseidonHash =
+                hex!("0001020304050607 08090a0b0c0d0e0f 0001020304050607 08090a0b0c0d0e0f");
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(deposit_size);
+            let res = slushie.deposit(deposit_size, hash);
+            assert!(res.is_ok());
+
+            let res = slushie.withdraw(
+                deposit_size,
+                PublicInputs {
+                    nullifier_hash: hash,
+                    root: slushie.get_root_hash(deposit_size).unwrap(),
+                    proof: [0; 1040],
+                    fee: deposit_size as u64 + 1,
+                    recipient: accounts.bob,
+                },
+            );
+            assert_eq!(res.unwrap_err(), Error::FeeExceedsDepositSize);
+        }
+
+        /// - can't withdraw funds with invalid root hash
+        #[ink::test]
+        fn withdraw_with_invalid_root_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let deposit_size = 13;
+            let mut slushie: Slushie = Slushie::new(vec![deposit_size], CHAIN_ID);
+            let hash: PoseidonHash =
+                hex!("0001020304050607 08090a0b0c0d0e0f 0001020304050607 08090a0b0c0d0e0f");
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(deposit_size);
+            let res = slushie.deposit(deposit_size, hash);
+            assert!(res.is_ok());
+
+            let invalid_root_hash: PoseidonHash =
+                hex!("0000000000000000 0000000000000000 0001020304050607 08090a0b0c0d0e0f");
+
+            let res = slushie.withdraw(
+                deposit_size,
+                PublicInputs {
+                    nullifier_hash: hash,
+                    root: invalid_root_hash,
+                    proof: [0; 1040],
+                    fee: 0,
+                    recipient: accounts.bob,
+                },
+            );
+            assert_eq!(res.unwrap_err(), Error::UnknownRoot);
+        }
+
+        /// - can't double withdraw funds with a proper deposit_size and a valid hash
+        #[ink::test]
+        fn withdraw_with_used_nullifier_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let deposit_size = 13;
+            let mut slushie: Slushie = Slushie::new(vec![deposit_size], CHAIN_ID);
+
+            let GeneratedCommitment {
+                nullifier,
+                randomness,
+                commitment_bytes,
+                nullifier_hash_bytes,
+            } = plonk_prover::generate_commitment();
+
+            let hash: PoseidonHash = commitment_bytes;
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(deposit_size);
+            let res = slushie.deposit(deposit_size, hash);
+            assert!(res.is_ok());
+
+            let resulting_root_hash = slushie.get_root_hash(deposit_size).unwrap();
+
+            let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> =
+                (&[commitment_bytes][..]).try_into().unwrap();
+
+            let tree_opening = tree.opening(0).unwrap();
 
             let proof = prove(
                 SERIALIZED_PUBLIC_PARAMETERS,
@@ -327,158 +2321,1279 @@ mod slushie {
                 randomness,
                 *accounts.alice.as_ref(),
                 *accounts.alice.as_ref(),
-                20,
+                10,
+                deposit_size,
+                slushie.domain(),
+            )
+            .unwrap();
+
+            let res = slushie.withdraw(
+                deposit_size,
+                PublicInputs {
+                    nullifier_hash: nullifier_hash_bytes,
+                    root: resulting_root_hash,
+                    proof,
+                    fee: 10,
+                    recipient: accounts.alice,
+                },
+            );
+            assert!(res.is_ok());
+
+            let res = slushie.withdraw(
+                deposit_size,
+                PublicInputs {
+                    nullifier_hash: nullifier_hash_bytes,
+                    root: resulting_root_hash,
+                    proof,
+                    fee: 10,
+                    recipient: accounts.alice,
+                },
+            );
+            assert_eq!(res.unwrap_err(), Error::NullifierAlreadyUsed);
+        }
+
+        /// - nullifier hash is shown as spent only after withdraw
+        #[ink::test]
+        fn is_spent_after_withdraw() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let deposit_size = 13;
+            let mut slushie: Slushie = Slushie::new(vec![deposit_size], CHAIN_ID);
+
+            let GeneratedCommitment {
+                nullifier,
+                randomness,
+                commitment_bytes,
+                nullifier_hash_bytes,
+            } = plonk_prover::generate_commitment();
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(deposit_size);
+            let resulting_root_hash = slushie.deposit(deposit_size, commitment_bytes).unwrap();
+
+            let unknown_nullifier_hash = [1; 32];
+            assert!(!slushie.is_spent(deposit_size, nullifier_hash_bytes));
+            assert_eq!(
+                slushie.are_spent(
+                    deposit_size,
+                    vec![nullifier_hash_bytes, unknown_nullifier_hash]
+                ),
+                vec![false, false]
+            );
+
+            let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> =
+                (&[commitment_bytes][..]).try_into().unwrap();
+
+            let proof = prove(
+                SERIALIZED_PUBLIC_PARAMETERS,
+                0,
+                resulting_root_hash,
+                tree.opening(0).unwrap(),
+                nullifier,
+                randomness,
+                *accounts.alice.as_ref(),
+                *accounts.alice.as_ref(),
+                0,
+                deposit_size,
+                slushie.domain(),
+            )
+            .unwrap();
+
+            let res = slushie.withdraw(
+                deposit_size,
+                PublicInputs {
+                    nullifier_hash: nullifier_hash_bytes,
+                    root: resulting_root_hash,
+                    proof,
+                    fee: 0,
+                    recipient: accounts.alice,
+                },
+            );
+            assert!(res.is_ok());
+
+            assert!(slushie.is_spent(deposit_size, nullifier_hash_bytes));
+            assert_eq!(
+                slushie.are_spent(
+                    deposit_size,
+                    vec![nullifier_hash_bytes, unknown_nullifier_hash]
+                ),
+                vec![true, false]
+            );
+        }
+
+        /// - pools with different denominations have their own trees
+        #[ink::test]
+        fn deposits_to_different_pools_are_separated() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let mut slushie: Slushie = Slushie::new(vec![13, 100], CHAIN_ID);
+
+            let initial_root_hash = slushie.get_root_hash(100).unwrap();
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+            let root = slushie.deposit(13, [1; 32]).unwrap();
+
+            assert_eq!(slushie.next_index(13).unwrap(), 1);
+            assert_eq!(slushie.next_index(100).unwrap(), 0);
+            assert_eq!(slushie.get_root_hash(100).unwrap(), initial_root_hash);
+            assert!(slushie.is_known_root(13, root));
+            assert!(!slushie.is_known_root(100, root));
+
+            // Deposit size of the other pool is not accepted
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+            let res = slushie.deposit(100, [2; 32]);
+            assert_eq!(res.unwrap_err(), Error::InvalidTransferredAmount);
+        }
+
+        /// - can't use a pool which is not created
+        #[ink::test]
+        fn unknown_pool_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(55);
+            let res = slushie.deposit(55, [1; 32]);
+            assert_eq!(res.unwrap_err(), Error::UnknownPool);
+
+            let res = slushie.withdraw(
+                55,
+                PublicInputs {
+                    nullifier_hash: [1; 32],
+                    root: slushie.get_root_hash(13).unwrap(),
+                    proof: [0; 1040],
+                    fee: 0,
+                    recipient: accounts.bob,
+                },
+            );
+            assert_eq!(res.unwrap_err(), Error::UnknownPool);
+
+            assert_eq!(slushie.next_index(55), Err(Error::UnknownPool));
+            assert_eq!(slushie.get_root_history(55), Err(Error::UnknownPool));
+            assert!(!slushie.is_known_root(55, slushie.get_root_hash(13).unwrap()));
+        }
+
+        /// Change of the pool or public inputs made after the proof is generated
+        type PublicInputsChange = fn(&mut Balance, &mut PublicInputs);
+
+        /// - proof can't be used to withdraw from another pool, for another contract or chain,
+        /// or for another recipient, which is equal to the original one modulo the field order
+        #[ink::test]
+        fn withdraw_with_proof_for_other_inputs_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+
+            let changes: [(&str, ChainId, PublicInputsChange); 3] = [
+                ("another pool", CHAIN_ID, |pool, _| *pool = 100),
+                // Same contract address on another chain
+                ("another domain", [8; 32], |_, _| {}),
+                // Recipient's account shifted by the field modulus
+                (
+                    "recipient equal modulo field order",
+                    CHAIN_ID,
+                    |_, inputs| {
+                        inputs.recipient = AccountId::from(add_modulus(*inputs.recipient.as_ref()))
+                    },
+                ),
+            ];
+            // Every case is deployed at its own address
+            let contracts = [accounts.charlie, accounts.django, accounts.eve];
+
+            for ((change_name, proof_chain_id, change), contract) in
+                changes.into_iter().zip(contracts)
+            {
+                ink_env::test::set_callee::<ink_env::DefaultEnvironment>(contract);
+                let proof_domain = withdrawal_domain(*contract.as_ref(), proof_chain_id);
+                // Native balance covers the deposits to both pools
+                ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(contract, 113);
+                let mut slushie: Slushie = Slushie::new(vec![13, 100], CHAIN_ID);
+                ink_env::test::set_caller::<Environment>(accounts.alice);
+
+                let mut pool = 13;
+                let mut public_inputs = deposit_to_all_pools_and_prove(
+                    &mut slushie,
+                    pool,
+                    accounts.alice,
+                    proof_domain,
+                );
+                change(&mut pool, &mut public_inputs);
+
+                let res = slushie.withdraw(pool, public_inputs.clone());
+                assert_eq!(
+                    res.unwrap_err(),
+                    Error::VerificationProofFailed,
+                    "proof is accepted for {}",
+                    change_name
+                );
+                assert!(!slushie.is_spent(pool, public_inputs.nullifier_hash));
+            }
+        }
+
+        /// - deposit to the PSP22 pool pulls the tokens with `transfer_from`
+        #[ink::test]
+        fn psp22_deposit_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let token = accounts.django;
+            let contract = ink_env::test::callee::<ink_env::DefaultEnvironment>();
+            psp22::mock::deploy(token);
+            psp22::mock::mint(token, accounts.bob, 100);
+            psp22::mock::approve(token, accounts.bob, contract, 13);
+
+            let mut slushie: Slushie = Slushie::new_psp22(vec![13], token, CHAIN_ID);
+            assert_eq!(slushie.asset(), Asset::Psp22(token));
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(0);
+            let res = slushie.deposit(13, [1; 32]);
+            assert!(res.is_ok());
+
+            assert_eq!(slushie.next_index(13).unwrap(), 1);
+            assert_eq!(psp22::balance_of(token, accounts.bob), Ok(87));
+            assert_eq!(psp22::balance_of(token, contract), Ok(13));
+        }
+
+        /// - can't deposit to the PSP22 pool without allowance, with native value or to a broken token
+        #[ink::test]
+        fn psp22_deposit_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let token = accounts.django;
+            psp22::mock::deploy(token);
+            psp22::mock::mint(token, accounts.bob, 100);
+
+            let mut slushie: Slushie = Slushie::new_psp22(vec![13], token, CHAIN_ID);
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(0);
+            let res = slushie.deposit(13, [1; 32]);
+            assert_eq!(
+                res.unwrap_err(),
+                Error::TokenTransferFailed(PSP22Error::InsufficientAllowance)
+            );
+
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+            let res = slushie.deposit(13, [1; 32]);
+            assert_eq!(res.unwrap_err(), Error::InvalidTransferredAmount);
+
+            assert_eq!(slushie.next_index(13).unwrap(), 0);
+            assert_eq!(psp22::balance_of(token, accounts.bob), Ok(100));
+
+            // Token contract which does not exist, deployed at another address
+            ink_env::test::set_callee::<ink_env::DefaultEnvironment>(accounts.eve);
+            let mut slushie: Slushie = Slushie::new_psp22(vec![13], accounts.frank, CHAIN_ID);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(0);
+            let res = slushie.deposit(13, [1; 32]);
+            assert_eq!(res.unwrap_err(), Error::TokenCallFailed);
+        }
+
+        /// - withdrawal from the PSP22 pool pays the tokens to recipient and relayer
+        #[ink::test]
+        fn psp22_withdraw_through_relayer_pays_fee() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let deposit_size: Balance = 13;
+            let fee: u64 = 5;
+            let token = accounts.django;
+            let contract = ink_env::test::callee::<ink_env::DefaultEnvironment>();
+            psp22::mock::deploy(token);
+            psp22::mock::mint(token, accounts.alice, deposit_size);
+            psp22::mock::approve(token, accounts.alice, contract, deposit_size);
+
+            let mut slushie: Slushie = Slushie::new_psp22(vec![deposit_size], token, CHAIN_ID);
+
+            let GeneratedCommitment {
+                nullifier,
+                randomness,
+                commitment_bytes,
+                nullifier_hash_bytes,
+            } = plonk_prover::generate_commitment();
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(0);
+            let root = slushie.deposit(deposit_size, commitment_bytes).unwrap();
+
+            let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> =
+                (&[commitment_bytes][..]).try_into().unwrap();
+
+            // Charlie is the recipient, Bob relays the withdrawal
+            let proof = prove(
+                SERIALIZED_PUBLIC_PARAMETERS,
+                0,
+                root,
+                tree.opening(0).unwrap(),
+                nullifier,
+                randomness,
+                *accounts.charlie.as_ref(),
+                *accounts.bob.as_ref(),
+                fee,
+                deposit_size,
+                slushie.domain(),
+            )
+            .unwrap();
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            let res = slushie.withdraw(
+                deposit_size,
+                PublicInputs {
+                    nullifier_hash: nullifier_hash_bytes,
+                    root,
+                    proof,
+                    fee,
+                    recipient: accounts.charlie,
+                },
+            );
+            assert!(res.is_ok());
+
+            assert_eq!(psp22::balance_of(token, contract), Ok(0));
+            assert_eq!(
+                psp22::balance_of(token, accounts.charlie),
+                Ok(deposit_size - fee as Balance)
+            );
+            assert_eq!(psp22::balance_of(token, accounts.bob), Ok(fee as Balance));
+        }
+
+        /// - deposit to the PSP34 pool takes custody of the token
+        #[ink::test]
+        fn psp34_deposit_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let collection = accounts.django;
+            let contract = ink_env::test::callee::<ink_env::DefaultEnvironment>();
+            psp34::mock::deploy(collection);
+            psp34::mock::mint(collection, accounts.bob, Id::U8(1));
+            psp34::mock::approve(collection, accounts.bob, contract, Id::U8(1));
+
+            let mut slushie: Slushie = Slushie::new_psp34(vec![13], collection, CHAIN_ID);
+            assert_eq!(slushie.asset(), Asset::Psp34(collection));
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+            let res = slushie.deposit_nft(13, [1; 32], Id::U8(1));
+            assert!(res.is_ok());
+
+            assert_eq!(slushie.next_index(13).unwrap(), 1);
+            assert_eq!(psp34::owner_of(collection, Id::U8(1)), Ok(Some(contract)));
+            assert_eq!(slushie.nft_inventory.get(13), Some(vec![Id::U8(1)]));
+        }
+
+        /// - can't deposit to the PSP34 pool without approval, fee reserve or with wrong message
+        #[ink::test]
+        fn psp34_deposit_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let collection = accounts.django;
+            psp34::mock::deploy(collection);
+            psp34::mock::mint(collection, accounts.bob, Id::U8(1));
+
+            let mut slushie: Slushie = Slushie::new_psp34(vec![13], collection, CHAIN_ID);
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+            let res = slushie.deposit_nft(13, [1; 32], Id::U8(1));
+            assert_eq!(
+                res.unwrap_err(),
+                Error::NftTransferFailed(PSP34Error::NotApproved)
+            );
+
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(0);
+            let res = slushie.deposit_nft(13, [1; 32], Id::U8(1));
+            assert_eq!(res.unwrap_err(), Error::InvalidTransferredAmount);
+
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+            let res = slushie.deposit(13, [1; 32]);
+            assert_eq!(res.unwrap_err(), Error::InvalidAsset);
+
+            assert_eq!(slushie.next_index(13).unwrap(), 0);
+            assert_eq!(
+                psp34::owner_of(collection, Id::U8(1)),
+                Ok(Some(accounts.bob))
+            );
+
+            // NFT can't be deposited to the native pool, deployed at another address
+            ink_env::test::set_callee::<ink_env::DefaultEnvironment>(accounts.eve);
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+            let res = slushie.deposit_nft(13, [1; 32], Id::U8(1));
+            assert_eq!(res.unwrap_err(), Error::InvalidAsset);
+        }
+
+        /// - only the owner of the token can deposit it, even when the contract is approved
+        #[ink::test]
+        fn psp34_deposit_by_not_owner_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let collection = accounts.django;
+            let contract = ink_env::test::callee::<ink_env::DefaultEnvironment>();
+            psp34::mock::deploy(collection);
+            psp34::mock::mint(collection, accounts.bob, Id::U8(1));
+            psp34::mock::approve(collection, accounts.bob, contract, Id::U8(1));
+
+            let mut slushie: Slushie = Slushie::new_psp34(vec![13], collection, CHAIN_ID);
+
+            // Charlie sees the approval and tries to deposit Bob's token with his commitment
+            ink_env::test::set_caller::<Environment>(accounts.charlie);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+            let res = slushie.deposit_nft(13, [1; 32], Id::U8(1));
+            assert_eq!(res.unwrap_err(), Error::NotNftOwner);
+
+            // Token which doesn't exist can't be deposited either
+            let res = slushie.deposit_nft(13, [1; 32], Id::U8(2));
+            assert_eq!(res.unwrap_err(), Error::NotNftOwner);
+
+            assert_eq!(slushie.next_index(13).unwrap(), 0);
+            assert_eq!(
+                psp34::owner_of(collection, Id::U8(1)),
+                Ok(Some(accounts.bob))
+            );
+            assert_eq!(slushie.nft_inventory.get(13), None);
+        }
+
+        /// - withdrawal from the PSP34 pool releases the token to recipient and pays fee to relayer
+        #[ink::test]
+        fn psp34_withdraw_releases_token() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let fee_reserve: Balance = 13;
+            let fee: u64 = 5;
+            let collection = accounts.django;
+            let contract = ink_env::test::callee::<ink_env::DefaultEnvironment>();
+            psp34::mock::deploy(collection);
+            psp34::mock::mint(collection, accounts.alice, Id::U8(1));
+            psp34::mock::approve(collection, accounts.alice, contract, Id::U8(1));
+
+            let mut slushie: Slushie = Slushie::new_psp34(vec![fee_reserve], collection, CHAIN_ID);
+
+            let GeneratedCommitment {
+                nullifier,
+                randomness,
+                commitment_bytes,
+                nullifier_hash_bytes,
+            } = plonk_prover::generate_commitment();
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(fee_reserve);
+            let root = slushie
+                .deposit_nft(fee_reserve, commitment_bytes, Id::U8(1))
+                .unwrap();
+
+            let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> =
+                (&[commitment_bytes][..]).try_into().unwrap();
+
+            // Charlie is the recipient, Bob relays the withdrawal
+            let proof = prove(
+                SERIALIZED_PUBLIC_PARAMETERS,
+                0,
+                root,
+                tree.opening(0).unwrap(),
+                nullifier,
+                randomness,
+                *accounts.charlie.as_ref(),
+                *accounts.bob.as_ref(),
+                fee,
+                fee_reserve,
+                slushie.domain(),
             )
             .unwrap();
 
-            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(deposit_size);
-            let res = slushie.withdraw(PublicInputs {
-                nullifier_hash: nullifier_hash_bytes,
-                root: resulting_root_hash,
-                proof,
-                fee: 20,
-                recipient: accounts.alice,
-            });
-            assert!(res.is_ok());
+            let recipient_balance =
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.charlie)
+                    .unwrap();
+            let relayer_balance =
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.bob)
+                    .unwrap();
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            let res = slushie.withdraw(
+                fee_reserve,
+                PublicInputs {
+                    nullifier_hash: nullifier_hash_bytes,
+                    root,
+                    proof,
+                    fee,
+                    recipient: accounts.charlie,
+                },
+            );
+            assert!(res.is_ok());
+
+            assert_eq!(
+                psp34::owner_of(collection, Id::U8(1)),
+                Ok(Some(accounts.charlie))
+            );
+            assert_eq!(slushie.nft_inventory.get(fee_reserve), Some(vec![]));
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.charlie)
+                    .unwrap(),
+                recipient_balance + fee_reserve - fee as Balance
+            );
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.bob)
+                    .unwrap(),
+                relayer_balance + fee as Balance
+            );
+        }
+
+        /// - batch withdraw from the PSP34 pool pays the withdrawals which can be covered
+        /// and reports the rest instead of trapping
+        #[ink::test]
+        fn psp34_withdraw_batch_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let fee_reserve: Balance = 13;
+            let collection = accounts.django;
+            let contract = ink_env::test::callee::<ink_env::DefaultEnvironment>();
+            psp34::mock::deploy(collection);
+            for id in [Id::U8(1), Id::U8(2)] {
+                psp34::mock::mint(collection, accounts.alice, id.clone());
+                psp34::mock::approve(collection, accounts.alice, contract, id);
+            }
+
+            let mut slushie: Slushie = Slushie::new_psp34(vec![fee_reserve], collection, CHAIN_ID);
+
+            let commitments: Vec<GeneratedCommitment> = (0..2)
+                .map(|_| plonk_prover::generate_commitment())
+                .collect();
+            let commitment_bytes: Vec<PoseidonHash> = commitments
+                .iter()
+                .map(|commitment| commitment.commitment_bytes)
+                .collect();
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(fee_reserve);
+            slushie
+                .deposit_nft(fee_reserve, commitment_bytes[0], Id::U8(1))
+                .unwrap();
+            let root = slushie
+                .deposit_nft(fee_reserve, commitment_bytes[1], Id::U8(2))
+                .unwrap();
+
+            let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> =
+                (&commitment_bytes[..]).try_into().unwrap();
+
+            // Charlie is the recipient, Bob relays the withdrawals
+            let batch: Vec<PublicInputs> = commitments
+                .iter()
+                .enumerate()
+                .map(|(i, commitment)| PublicInputs {
+                    nullifier_hash: commitment.nullifier_hash_bytes,
+                    root,
+                    proof: prove(
+                        SERIALIZED_PUBLIC_PARAMETERS,
+                        i,
+                        root,
+                        tree.opening(i).unwrap(),
+                        commitment.nullifier,
+                        commitment.randomness,
+                        *accounts.charlie.as_ref(),
+                        *accounts.bob.as_ref(),
+                        0,
+                        fee_reserve,
+                        slushie.domain(),
+                    )
+                    .unwrap(),
+                    fee: 0,
+                    recipient: accounts.charlie,
+                })
+                .collect();
+
+            // Native balance covers the fee reserve of one withdrawal only
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(
+                contract,
+                fee_reserve,
+            );
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            let results = slushie
+                .withdraw_batch(fee_reserve, batch.clone(), false)
+                .unwrap();
+            assert_eq!(results, vec![Ok(()), Err(Error::InsufficientFunds)]);
+
+            assert!(slushie.is_spent(fee_reserve, batch[0].nullifier_hash));
+            assert!(!slushie.is_spent(fee_reserve, batch[1].nullifier_hash));
+            assert_eq!(
+                psp34::owner_of(collection, Id::U8(2)),
+                Ok(Some(accounts.charlie))
+            );
+            assert_eq!(psp34::owner_of(collection, Id::U8(1)), Ok(Some(contract)));
+            assert_eq!(
+                slushie.nft_inventory.get(fee_reserve),
+                Some(vec![Id::U8(1)])
+            );
+
+            // The rest is paid once the contract has the fee reserve
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(
+                contract,
+                fee_reserve,
+            );
+            let results = slushie
+                .withdraw_batch(fee_reserve, batch[1..].to_vec(), true)
+                .unwrap();
+            assert_eq!(results, vec![Ok(())]);
+            assert_eq!(
+                psp34::owner_of(collection, Id::U8(1)),
+                Ok(Some(accounts.charlie))
+            );
+        }
+
+        /// - owner is the deployer and can transfer the ownership
+        #[ink::test]
+        fn transfer_ownership_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+            assert_eq!(slushie.owner(), accounts.alice);
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                slushie.transfer_ownership(accounts.bob).unwrap_err(),
+                Error::NotOwner
+            );
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            assert!(slushie.transfer_ownership(accounts.bob).is_ok());
+            assert_eq!(slushie.owner(), accounts.bob);
+
+            // Previous owner can't manage the contract anymore
+            assert_eq!(
+                slushie.set_deposits_paused(true).unwrap_err(),
+                Error::NotOwner
+            );
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 1);
+            if let Event::OwnershipTransferred(OwnershipTransferred {
+                previous_owner,
+                new_owner,
+            }) = decode_event(&emitted_events[0])
+            {
+                assert_eq!(previous_owner, accounts.alice);
+                assert_eq!(new_owner, accounts.bob);
+            } else {
+                panic!("OwnershipTransferred event is expected");
+            }
         }
 
-        /// - can withdraw funds with a proper deposit_size and hash by different account
+        /// - paused deposits are rejected, while withdrawals are still processed
         #[ink::test]
-        fn withdraw_from_different_account_works() {
+        fn paused_deposits_fail() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
-            let deposit_size = 13;
-            let mut slushie: Slushie = Slushie::new(deposit_size);
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
 
-            let GeneratedCommitment {
-                nullifier,
-                randomness,
-                commitment_bytes,
-                nullifier_hash_bytes,
-            } = plonk_prover::generate_commitment();
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                slushie.set_deposits_paused(true).unwrap_err(),
+                Error::NotOwner
+            );
 
-            let hash: PoseidonHash = commitment_bytes;
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            assert!(slushie.set_deposits_paused(true).is_ok());
+            assert!(slushie.deposits_paused());
+            assert!(!slushie.withdrawals_paused());
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+            let res = slushie.deposit(13, [1; 32]);
+            assert_eq!(res.unwrap_err(), Error::DepositsPaused);
+            assert_eq!(slushie.next_index(13).unwrap(), 0);
+
+            // Withdraw is not paused and fails only on the root check
+            let res = slushie.withdraw(
+                13,
+                PublicInputs {
+                    nullifier_hash: [1; 32],
+                    root: [1; 32],
+                    proof: [0; 1040],
+                    fee: 0,
+                    recipient: accounts.bob,
+                },
+            );
+            assert_eq!(res.unwrap_err(), Error::UnknownRoot);
 
             ink_env::test::set_caller::<Environment>(accounts.alice);
-            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(deposit_size);
-            let res = slushie.deposit(hash);
-            assert!(res.is_ok());
+            assert!(slushie.set_deposits_paused(false).is_ok());
 
-            let resulting_root_hash = slushie.get_root_hash();
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            assert!(slushie.deposit(13, [1; 32]).is_ok());
 
-            let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> =
-                (&[commitment_bytes][..]).try_into().unwrap();
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            if let Event::PauseChanged(PauseChanged {
+                deposits_paused,
+                withdrawals_paused,
+            }) = decode_event(&emitted_events[0])
+            {
+                assert!(deposits_paused);
+                assert!(!withdrawals_paused);
+            } else {
+                panic!("PauseChanged event is expected");
+            }
+        }
 
-            let tree_opening = tree.get_opening(0).unwrap();
+        /// - paused withdrawals are rejected
+        #[ink::test]
+        fn paused_withdrawals_fail() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
 
-            let proof = prove(
-                SERIALIZED_PUBLIC_PARAMETERS,
-                0,
-                resulting_root_hash,
-                tree_opening,
-                nullifier,
-                randomness,
-                *accounts.eve.as_ref(),
-                *accounts.eve.as_ref(),
-                0,
-            )
-            .unwrap();
+            assert!(slushie.set_withdrawals_paused(true).is_ok());
+            assert!(slushie.withdrawals_paused());
 
-            ink_env::test::set_caller::<Environment>(accounts.eve);
-            let res = slushie.withdraw(PublicInputs {
-                nullifier_hash: nullifier_hash_bytes,
-                root: resulting_root_hash,
-                proof,
-                fee: 0,
-                recipient: accounts.eve,
-            });
-            assert!(res.is_ok());
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+            let root = slushie.deposit(13, [1; 32]).unwrap();
+
+            let res = slushie.withdraw(
+                13,
+                PublicInputs {
+                    nullifier_hash: [1; 32],
+                    root,
+                    proof: [0; 1040],
+                    fee: 0,
+                    recipient: accounts.bob,
+                },
+            );
+            assert_eq!(res.unwrap_err(), Error::WithdrawalsPaused);
         }
 
-        /// - can't withdraw funds with invalid root hash
+        /// - verifying key bundled with the contract is compiled for the current circuit,
+        ///   regenerate it with `generate-verifier-data` when the circuit changes
         #[ink::test]
-        fn withdraw_with_invalid_root_fails() {
+        fn bundled_verifying_key_matches_circuit() {
+            let (verifier_data, opening_key) =
+                generate_verifier_data(SERIALIZED_PUBLIC_PARAMETERS).unwrap();
+
+            assert_eq!(SERIALIZED_VD, &verifier_data[..]);
+            assert_eq!(SERIALIZED_OPENING_KEY, &opening_key);
+        }
+
+        /// - verifying key can be replaced only after the delay
+        #[ink::test]
+        fn verifying_key_upgrade_is_time_locked() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
-            let deposit_size = 13;
-            let mut slushie: Slushie = Slushie::new(deposit_size);
-            let hash: PoseidonHash =
-                hex!("0001020304050607 08090a0b0c0d0e0f 0001020304050607 08090a0b0c0d0e0f");
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+            slushie.key_upgrade_delay = 60;
+
+            let initial_key_hash = slushie.verifying_key_hash();
+            assert_eq!(
+                slushie
+                    .verifying_key(initial_key_hash)
+                    .unwrap()
+                    .verifier_data,
+                SERIALIZED_VD.to_vec()
+            );
+            assert_eq!(slushie.proposed_verifying_key(), None);
+            assert_eq!(slushie.previous_verifying_key(), None);
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                slushie
+                    .propose_verifying_key(broken_verifying_key())
+                    .unwrap_err(),
+                Error::NotOwner
+            );
 
             ink_env::test::set_caller::<Environment>(accounts.alice);
-            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(deposit_size);
-            let res = slushie.deposit(hash);
-            assert!(res.is_ok());
+            let invalid_key = VerifyingKey {
+                verifier_data: SERIALIZED_VD.to_vec(),
+                opening_key: vec![0; 10],
+            };
+            assert_eq!(
+                slushie.propose_verifying_key(invalid_key).unwrap_err(),
+                Error::InvalidVerifyingKey
+            );
 
-            let invalid_root_hash: PoseidonHash =
-                hex!("0000000000000000 0000000000000000 0001020304050607 08090a0b0c0d0e0f");
+            // Key which can't be deserialized can't become an upgrade
+            let malformed_key = VerifyingKey {
+                verifier_data: vec![1; SERIALIZED_VD.len()],
+                opening_key: SERIALIZED_OPENING_KEY.to_vec(),
+            };
+            assert_eq!(
+                slushie.propose_verifying_key(malformed_key).unwrap_err(),
+                Error::InvalidVerifyingKey
+            );
 
-            let res = slushie.withdraw(PublicInputs {
-                nullifier_hash: hash,
-                root: invalid_root_hash,
-                proof: [0; 1040],
-                fee: 0,
-                recipient: accounts.bob,
-            });
-            assert_eq!(res.unwrap_err(), Error::UnknownRoot);
+            // Key for another tree depth can't be used
+            let (verifier_data, opening_key) =
+                generate_verifier_data_with_depth::<10>(SERIALIZED_PUBLIC_PARAMETERS).unwrap();
+            let key_for_another_depth = VerifyingKey {
+                verifier_data,
+                opening_key: opening_key.to_vec(),
+            };
+            assert_eq!(
+                slushie
+                    .propose_verifying_key(key_for_another_depth)
+                    .unwrap_err(),
+                Error::InvalidVerifyingKey
+            );
+            assert_eq!(
+                slushie.execute_verifying_key_upgrade().unwrap_err(),
+                Error::NoKeyProposal
+            );
+
+            let proposed_at = ink_env::block_timestamp::<ink_env::DefaultEnvironment>();
+            let key_hash = slushie
+                .propose_verifying_key(broken_verifying_key())
+                .unwrap();
+            assert_eq!(
+                slushie.proposed_verifying_key(),
+                Some((key_hash, proposed_at + 60))
+            );
+
+            assert_eq!(
+                slushie.execute_verifying_key_upgrade().unwrap_err(),
+                Error::KeyUpgradeTooEarly
+            );
+            assert_eq!(slushie.verifying_key_hash(), initial_key_hash);
+
+            advance_time_to(proposed_at + 60);
+            assert!(slushie.execute_verifying_key_upgrade().is_ok());
+
+            let upgraded_at = ink_env::block_timestamp::<ink_env::DefaultEnvironment>();
+            assert_eq!(slushie.verifying_key_hash(), key_hash);
+            assert_eq!(slushie.proposed_verifying_key(), None);
+            assert_eq!(
+                slushie.previous_verifying_key(),
+                Some((initial_key_hash, upgraded_at + DEFAULT_KEY_GRACE_PERIOD))
+            );
+
+            // Delay can't be decreased
+            assert_eq!(
+                slushie.set_key_upgrade_delay(10).unwrap_err(),
+                Error::InvalidKeyUpgradeDelay
+            );
+            assert!(slushie.set_key_upgrade_delay(120).is_ok());
+            assert_eq!(slushie.key_upgrade_delay(), 120);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 2);
+
+            if let Event::VerifyingKeyProposed(VerifyingKeyProposed {
+                key_hash: proposed_key_hash,
+                executable_at,
+            }) = decode_event(&emitted_events[0])
+            {
+                assert_eq!(proposed_key_hash, key_hash);
+                assert_eq!(executable_at, proposed_at + 60);
+            } else {
+                panic!("VerifyingKeyProposed event is expected");
+            }
+
+            if let Event::VerifyingKeyUpgraded(VerifyingKeyUpgraded {
+                key_hash: upgraded_key_hash,
+                previous_key_hash,
+                ..
+            }) = decode_event(&emitted_events[1])
+            {
+                assert_eq!(upgraded_key_hash, key_hash);
+                assert_eq!(previous_key_hash, initial_key_hash);
+            } else {
+                panic!("VerifyingKeyUpgraded event is expected");
+            }
         }
 
-        /// - can't double withdraw funds with a proper deposit_size and a valid hash
+        /// - proposed verifying key can be cancelled
         #[ink::test]
-        fn withdraw_with_used_nullifier_fails() {
+        fn verifying_key_proposal_can_be_cancelled() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
-            let deposit_size = 13;
-            let mut slushie: Slushie = Slushie::new(deposit_size);
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+            slushie.key_upgrade_delay = 0;
 
-            let GeneratedCommitment {
-                nullifier,
-                randomness,
-                commitment_bytes,
-                nullifier_hash_bytes,
-            } = plonk_prover::generate_commitment();
+            assert_eq!(
+                slushie.cancel_verifying_key_proposal().unwrap_err(),
+                Error::NoKeyProposal
+            );
 
-            let hash: PoseidonHash = commitment_bytes;
+            let key_hash = slushie
+                .propose_verifying_key(broken_verifying_key())
+                .unwrap();
+            assert!(slushie.cancel_verifying_key_proposal().is_ok());
+
+            assert_eq!(slushie.proposed_verifying_key(), None);
+            assert_eq!(slushie.verifying_key(key_hash), None);
+            assert_eq!(
+                slushie.execute_verifying_key_upgrade().unwrap_err(),
+                Error::NoKeyProposal
+            );
+        }
 
+        /// - replaced verifying keys are removed from storage
+        #[ink::test]
+        fn replaced_verifying_keys_are_removed() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
             ink_env::test::set_caller::<Environment>(accounts.alice);
-            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(deposit_size);
-            let res = slushie.deposit(hash);
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+            slushie.key_upgrade_delay = 0;
+            let initial_key_hash = slushie.verifying_key_hash();
+
+            // New proposal replaces the previous one
+            let replaced_key_hash = slushie
+                .propose_verifying_key(broken_verifying_key())
+                .unwrap();
+            let first_key_hash = slushie
+                .propose_verifying_key(broken_verifying_key())
+                .unwrap();
+            assert_eq!(slushie.verifying_key(replaced_key_hash), None);
+            assert!(slushie.verifying_key(first_key_hash).is_some());
+
+            // Initial key is kept as the previous one after the first upgrade
+            slushie.execute_verifying_key_upgrade().unwrap();
+            assert!(slushie.verifying_key(initial_key_hash).is_some());
+
+            // and is removed when the next upgrade replaces the previous key
+            let second_key_hash = slushie
+                .propose_verifying_key(broken_verifying_key())
+                .unwrap();
+            slushie.execute_verifying_key_upgrade().unwrap();
+            assert_eq!(slushie.verifying_key(initial_key_hash), None);
+            assert!(slushie.verifying_key(first_key_hash).is_some());
+            assert!(slushie.verifying_key(second_key_hash).is_some());
+        }
+
+        /// - previous verifying key is accepted during the grace period from the constructor,
+        /// which can be changed only by the owner for the next upgrades
+        #[ink::test]
+        fn verifying_key_upgrade_uses_configured_grace_period() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            let mut slushie: Slushie = Slushie::new_with_tree_config(
+                Asset::Native,
+                vec![13],
+                TreeConfig::default(),
+                None,
+                Some(60),
+                CHAIN_ID,
+            );
+            slushie.key_upgrade_delay = 0;
+            assert_eq!(slushie.key_grace_period(), 60);
+
+            let initial_key_hash = slushie.verifying_key_hash();
+            slushie
+                .propose_verifying_key(broken_verifying_key())
+                .unwrap();
+            slushie.execute_verifying_key_upgrade().unwrap();
+            let upgraded_at = ink_env::block_timestamp::<ink_env::DefaultEnvironment>();
+            assert_eq!(
+                slushie.previous_verifying_key(),
+                Some((initial_key_hash, upgraded_at + 60))
+            );
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                slushie.set_key_grace_period(120).unwrap_err(),
+                Error::NotOwner
+            );
+
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            assert!(slushie.set_key_grace_period(120).is_ok());
+            assert_eq!(slushie.key_grace_period(), 120);
+
+            // New period is applied to the next upgrade only
+            assert_eq!(
+                slushie.previous_verifying_key(),
+                Some((initial_key_hash, upgraded_at + 60))
+            );
+
+            let first_key_hash = slushie.verifying_key_hash();
+            slushie
+                .propose_verifying_key(broken_verifying_key())
+                .unwrap();
+            slushie.execute_verifying_key_upgrade().unwrap();
+            let upgraded_at = ink_env::block_timestamp::<ink_env::DefaultEnvironment>();
+            assert_eq!(
+                slushie.previous_verifying_key(),
+                Some((first_key_hash, upgraded_at + 120))
+            );
+        }
+
+        /// - proofs for the previous verifying key are accepted only during the grace period
+        #[ink::test]
+        fn previous_verifying_key_is_accepted_during_grace_period() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+            slushie.key_upgrade_delay = 0;
+            slushie.set_key_grace_period(60).unwrap();
+
+            let commitments = [
+                plonk_prover::generate_commitment(),
+                plonk_prover::generate_commitment(),
+            ];
+
+            let mut root = [0; 32];
+            for commitment in &commitments {
+                ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+                root = slushie.deposit(13, commitment.commitment_bytes).unwrap();
+            }
+
+            let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> = (&[
+                commitments[0].commitment_bytes,
+                commitments[1].commitment_bytes,
+            ][..])
+                .try_into()
+                .unwrap();
+
+            let public_inputs: Vec<PublicInputs> = commitments
+                .iter()
+                .enumerate()
+                .map(|(i, commitment)| PublicInputs {
+                    nullifier_hash: commitment.nullifier_hash_bytes,
+                    root,
+                    proof: prove(
+                        SERIALIZED_PUBLIC_PARAMETERS,
+                        i,
+                        root,
+                        tree.opening(i).unwrap(),
+                        commitment.nullifier,
+                        commitment.randomness,
+                        *accounts.alice.as_ref(),
+                        *accounts.alice.as_ref(),
+                        0,
+                        13,
+                        slushie.domain(),
+                    )
+                    .unwrap(),
+                    fee: 0,
+                    recipient: accounts.alice,
+                })
+                .collect();
+
+            slushie
+                .propose_verifying_key(broken_verifying_key())
+                .unwrap();
+            assert!(slushie.execute_verifying_key_upgrade().is_ok());
+            let upgraded_at = ink_env::block_timestamp::<ink_env::DefaultEnvironment>();
+
+            let res = slushie.withdraw(13, public_inputs[0].clone());
             assert!(res.is_ok());
 
-            let resulting_root_hash = slushie.get_root_hash();
+            advance_time_to(upgraded_at + 60);
+            let res = slushie.withdraw(13, public_inputs[1].clone());
+            assert_eq!(res.unwrap_err(), Error::VerificationProofFailed);
+        }
+
+        /// - batch deposit inserts all commitments and emits event for every leaf
+        #[ink::test]
+        fn deposit_batch_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+            let commitments = vec![[1; 32], [2; 32], [3; 32]];
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(39);
+            let root = slushie.deposit_batch(13, commitments.clone()).unwrap();
 
             let tree: plonk_prover::merkle_tree::MerkleTree<DEFAULT_DEPTH, Poseidon> =
-                (&[commitment_bytes][..]).try_into().unwrap();
+                (&commitments[..]).try_into().unwrap();
+            assert_eq!(root, tree.root());
+            assert_eq!(slushie.get_root_hash(13).unwrap(), root);
+            assert_eq!(slushie.next_index(13).unwrap(), 3);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 3);
+
+            for (i, event) in emitted_events.iter().enumerate() {
+                if let Event::Deposited(Deposited {
+                    hash, leaf_index, ..
+                }) = decode_event(event)
+                {
+                    assert_eq!(hash, commitments[i]);
+                    assert_eq!(leaf_index, i as u64);
+                } else {
+                    panic!("Deposited event is expected");
+                }
+            }
+        }
 
-            let tree_opening = tree.get_opening(0).unwrap();
+        /// - can't deposit a batch with an invalid amount or without commitments
+        #[ink::test]
+        fn deposit_batch_invalid_amount_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
 
-            let proof = prove(
-                SERIALIZED_PUBLIC_PARAMETERS,
-                0,
-                resulting_root_hash,
-                tree_opening,
-                nullifier,
-                randomness,
-                *accounts.alice.as_ref(),
-                *accounts.alice.as_ref(),
-                10,
-            )
-            .unwrap();
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+            let res = slushie.deposit_batch(13, vec![[1; 32], [2; 32]]);
+            assert_eq!(res.unwrap_err(), Error::InvalidTransferredAmount);
 
-            let res = slushie.withdraw(PublicInputs {
-                nullifier_hash: nullifier_hash_bytes,
-                root: resulting_root_hash,
-                proof,
-                fee: 10,
-                recipient: accounts.alice,
-            });
-            assert!(res.is_ok());
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(0);
+            let res = slushie.deposit_batch(13, vec![]);
+            assert_eq!(res.unwrap_err(), Error::EmptyBatch);
 
-            let res = slushie.withdraw(PublicInputs {
-                nullifier_hash: nullifier_hash_bytes,
-                root: resulting_root_hash,
-                proof,
-                fee: 10,
-                recipient: accounts.alice,
-            });
-            assert_eq!(res.unwrap_err(), Error::NullifierAlreadyUsed);
+            assert_eq!(slushie.next_index(13).unwrap(), 0);
+        }
+
+        /// - batch deposit continues in the next epoch if the tree fills up partway through
+        #[ink::test]
+        fn deposit_batch_into_almost_full_tree_rolls_over() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+
+            // Only one leaf is left in the tree
+            let mut merkle_tree = slushie.pool_tree(13).unwrap();
+            merkle_tree.next_index = (1 << DEFAULT_DEPTH) - 1;
+            slushie.merkle_trees.insert(13, &merkle_tree);
+
+            ink_env::test::set_caller::<Environment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(26);
+            let root = slushie.deposit_batch(13, vec![[1; 32], [2; 32]]).unwrap();
+
+            assert_eq!(slushie.current_epoch(13).unwrap(), 1);
+            assert_eq!(slushie.next_index(13).unwrap(), 1);
+            assert_eq!(slushie.get_root_hash(13).unwrap(), root);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 2);
+
+            let deposits: Vec<(EpochId, u64, PoseidonHash)> = emitted_events
+                .iter()
+                .map(|event| match decode_event(event) {
+                    Event::Deposited(Deposited {
+                        epoch,
+                        leaf_index,
+                        root,
+                        ..
+                    }) => (epoch, leaf_index, root),
+                    _ => panic!("Deposited event is expected"),
+                })
+                .collect();
+            assert_eq!(deposits[0].0, 0);
+            assert_eq!(deposits[0].1, (1 << DEFAULT_DEPTH) - 1);
+            assert_eq!(deposits[1], (1, 0, root));
+
+            // Final roots of the full tree are archived
+            let archived_history = slushie.get_epoch_root_history(13, 0).unwrap();
+            assert_eq!(archived_history.len(), DEFAULT_ROOT_HISTORY_SIZE);
+            assert_eq!(archived_history.last(), Some(&deposits[0].2));
+            assert_eq!(slushie.root_epoch(13, deposits[0].2), Some(0));
+            assert_eq!(slushie.root_epoch(13, root), Some(1));
+            assert!(slushie.is_known_root(13, deposits[0].2));
+
+            assert_eq!(
+                slushie.get_epoch_root_history(13, 1).unwrap(),
+                slushie.get_root_history(13).unwrap()
+            );
+            assert_eq!(
+                slushie.get_epoch_root_history(13, 2).unwrap_err(),
+                Error::UnknownEpoch
+            );
+        }
+
+        /// - deposit of the earlier epoch can be withdrawn after the tree rolled over
+        #[ink::test]
+        fn withdraw_from_archived_epoch_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+
+            let public_inputs = deposit_and_prove(&mut slushie, 13, accounts.alice, 1).remove(0);
+
+            // Fill the tree, so the next deposit starts epoch 1
+            let mut merkle_tree = slushie.pool_tree(13).unwrap();
+            merkle_tree.next_index = 1 << DEFAULT_DEPTH;
+            slushie.merkle_trees.insert(13, &merkle_tree);
+
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(13);
+            slushie.deposit(13, [1; 32]).unwrap();
+            assert_eq!(slushie.current_epoch(13).unwrap(), 1);
+            assert_eq!(slushie.root_epoch(13, public_inputs.root), Some(0));
+
+            slushie.withdraw(13, public_inputs).unwrap();
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            if let Event::Withdrawn(Withdrawn { epoch, .. }) =
+                decode_event(emitted_events.last().unwrap())
+            {
+                assert_eq!(epoch, 0);
+            } else {
+                panic!("Withdrawn event is expected");
+            }
+        }
+
+        /// - batch withdraw pays valid withdrawals and returns result for every one
+        #[ink::test]
+        fn withdraw_batch_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+
+            let mut batch = deposit_and_prove(&mut slushie, 13, accounts.alice, 2);
+
+            let mut invalid = batch[0].clone();
+            invalid.nullifier_hash = [1; 32];
+            invalid.root = [1; 32];
+            batch.push(invalid);
+
+            let mut invalid_proof = batch[1].clone();
+            invalid_proof.nullifier_hash = [2; 32];
+            batch.push(invalid_proof);
+
+            let results = slushie.withdraw_batch(13, batch.clone(), false).unwrap();
+            assert_eq!(
+                results,
+                vec![
+                    Ok(()),
+                    Ok(()),
+                    Err(Error::UnknownRoot),
+                    Err(Error::VerificationProofFailed)
+                ]
+            );
+
+            assert_eq!(
+                slushie.are_spent(
+                    13,
+                    batch
+                        .iter()
+                        .map(|public_inputs| public_inputs.nullifier_hash)
+                        .collect()
+                ),
+                vec![true, true, false, false]
+            );
+
+            // Already used nullifier is reported for its withdrawal
+            let results = slushie
+                .withdraw_batch(13, batch[..1].to_vec(), false)
+                .unwrap();
+            assert_eq!(results, vec![Err(Error::NullifierAlreadyUsed)]);
+        }
+
+        /// - atomic batch withdraw pays nothing if any withdrawal is invalid
+        #[ink::test]
+        fn atomic_withdraw_batch_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            ink_env::test::set_caller::<Environment>(accounts.alice);
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+
+            let batch = deposit_and_prove(&mut slushie, 13, accounts.alice, 2);
+
+            let mut invalid = batch[1].clone();
+            invalid.fee = 1;
+            let res = slushie.withdraw_batch(13, vec![batch[0].clone(), invalid], true);
+            assert_eq!(res.unwrap_err(), Error::VerificationProofFailed);
+            assert!(!slushie.is_spent(13, batch[0].nullifier_hash));
+            assert_eq!(ink_env::test::recorded_events().count(), 2);
+
+            let results = slushie.withdraw_batch(13, batch.clone(), true).unwrap();
+            assert_eq!(results, vec![Ok(()), Ok(())]);
+            assert!(slushie.is_spent(13, batch[0].nullifier_hash));
+            assert!(slushie.is_spent(13, batch[1].nullifier_hash));
+        }
+
+        /// - can't withdraw a batch with duplicated nullifier hashes or without withdrawals
+        #[ink::test]
+        fn withdraw_batch_with_duplicated_nullifier_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>();
+            let mut slushie: Slushie = Slushie::new(vec![13], CHAIN_ID);
+
+            let public_inputs = PublicInputs {
+                nullifier_hash: [1; 32],
+                root: slushie.get_root_hash(13).unwrap(),
+                proof: [0; 1040],
+                fee: 0,
+                recipient: accounts.bob,
+            };
+
+            for atomic in [false, true] {
+                let res = slushie.withdraw_batch(
+                    13,
+                    vec![public_inputs.clone(), public_inputs.clone()],
+                    atomic,
+                );
+                assert_eq!(res.unwrap_err(), Error::DuplicateNullifier);
+
+                let res = slushie.withdraw_batch(13, vec![], atomic);
+                assert_eq!(res.unwrap_err(), Error::EmptyBatch);
+            }
         }
     }
 }
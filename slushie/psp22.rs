@@ -0,0 +1,205 @@
+//! Cross-contract calls to the PSP22 token of the pools
+//!
+//! Off-chain tests can't make cross-contract calls,
+//! so in tests the calls go to the mock PSP22 ledger instead.
+
+use crate::utils::TokenError;
+use ink_env::{AccountId, DefaultEnvironment, Environment};
+use ink_prelude::string::String;
+
+type Balance = <DefaultEnvironment as Environment>::Balance;
+
+/// Result of the call to the PSP22 token
+pub(crate) type CallResult<T> = Result<T, TokenError<PSP22Error>>;
+
+/// Errors returned by the PSP22 token, as they are defined in the PSP22 standard
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum PSP22Error {
+    Custom(String),
+    InsufficientBalance,
+    InsufficientAllowance,
+    ZeroRecipientAddress,
+    ZeroSenderAddress,
+    SafeTransferCheckFailed(String),
+}
+
+#[cfg(not(test))]
+pub(crate) use calls::*;
+#[cfg(test)]
+pub(crate) use mock::{balance_of, transfer, transfer_from};
+
+#[cfg(not(test))]
+mod calls {
+    use super::*;
+    use ink_env::call::{build_call, Call, ExecutionInput, Selector};
+    use ink_prelude::vec::Vec;
+
+    /// Selector of `PSP22::balance_of`
+    const BALANCE_OF_SELECTOR: [u8; 4] = [0x65, 0x68, 0x38, 0x2f];
+    /// Selector of `PSP22::transfer`
+    const TRANSFER_SELECTOR: [u8; 4] = [0xdb, 0x20, 0xf9, 0xf5];
+    /// Selector of `PSP22::transfer_from`
+    const TRANSFER_FROM_SELECTOR: [u8; 4] = [0x54, 0xb3, 0xc7, 0x6e];
+
+    /// Returns the token balance of `owner`
+    pub(crate) fn balance_of(token: AccountId, owner: AccountId) -> CallResult<Balance> {
+        build_call::<DefaultEnvironment>()
+            .call_type(Call::new().callee(token))
+            .exec_input(ExecutionInput::new(Selector::new(BALANCE_OF_SELECTOR)).push_arg(owner))
+            .returns::<Balance>()
+            .fire()
+            .map_err(|_| TokenError::CallFailed)
+    }
+
+    /// Transfers `value` tokens from the contract to `to`
+    pub(crate) fn transfer(token: AccountId, to: AccountId, value: Balance) -> CallResult<()> {
+        build_call::<DefaultEnvironment>()
+            .call_type(Call::new().callee(token))
+            .exec_input(
+                ExecutionInput::new(Selector::new(TRANSFER_SELECTOR))
+                    .push_arg(to)
+                    .push_arg(value)
+                    .push_arg(Vec::<u8>::new()),
+            )
+            .returns::<Result<(), PSP22Error>>()
+            .fire()
+            .map_err(|_| TokenError::CallFailed)?
+            .map_err(TokenError::Rejected)
+    }
+
+    /// Transfers `value` tokens from `from` to `to` using the allowance given to the contract
+    pub(crate) fn transfer_from(
+        token: AccountId,
+        from: AccountId,
+        to: AccountId,
+        value: Balance,
+    ) -> CallResult<()> {
+        build_call::<DefaultEnvironment>()
+            .call_type(Call::new().callee(token))
+            .exec_input(
+                ExecutionInput::new(Selector::new(TRANSFER_FROM_SELECTOR))
+                    .push_arg(from)
+                    .push_arg(to)
+                    .push_arg(value)
+                    .push_arg(Vec::<u8>::new()),
+            )
+            .returns::<Result<(), PSP22Error>>()
+            .fire()
+            .map_err(|_| TokenError::CallFailed)?
+            .map_err(TokenError::Rejected)
+    }
+}
+
+/// Mock PSP22 ledger for the off-chain tests
+///
+/// Only tokens created with `deploy` can be called, the caller of
+/// `transfer` and `transfer_from` is the contract under test
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+
+    #[derive(Default)]
+    struct Token {
+        balances: BTreeMap<AccountId, Balance>,
+        allowances: BTreeMap<(AccountId, AccountId), Balance>,
+    }
+
+    thread_local! {
+        static TOKENS: RefCell<BTreeMap<AccountId, Token>> = const { RefCell::new(BTreeMap::new()) };
+    }
+
+    fn with_token<R>(
+        token: AccountId,
+        f: impl FnOnce(&mut Token) -> CallResult<R>,
+    ) -> CallResult<R> {
+        TOKENS.with(|tokens| {
+            tokens
+                .borrow_mut()
+                .get_mut(&token)
+                .ok_or(TokenError::CallFailed)
+                .and_then(f)
+        })
+    }
+
+    fn contract() -> AccountId {
+        ink_env::account_id::<DefaultEnvironment>()
+    }
+
+    fn move_tokens(
+        token: &mut Token,
+        from: AccountId,
+        to: AccountId,
+        value: Balance,
+    ) -> CallResult<()> {
+        let from_balance = token.balances.get(&from).copied().unwrap_or(0);
+        if from_balance < value {
+            return Err(TokenError::Rejected(PSP22Error::InsufficientBalance));
+        }
+
+        token.balances.insert(from, from_balance - value);
+        *token.balances.entry(to).or_insert(0) += value;
+
+        Ok(())
+    }
+
+    /// Creates a token without any balances
+    pub(crate) fn deploy(token: AccountId) {
+        TOKENS.with(|tokens| tokens.borrow_mut().insert(token, Token::default()));
+    }
+
+    /// Mints `value` tokens to `owner`
+    pub(crate) fn mint(token: AccountId, owner: AccountId, value: Balance) {
+        with_token(token, |token| {
+            *token.balances.entry(owner).or_insert(0) += value;
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    /// Sets the allowance of `spender` given by `owner`
+    pub(crate) fn approve(token: AccountId, owner: AccountId, spender: AccountId, value: Balance) {
+        with_token(token, |token| {
+            token.allowances.insert((owner, spender), value);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    pub(crate) fn balance_of(token: AccountId, owner: AccountId) -> CallResult<Balance> {
+        with_token(token, |token| {
+            Ok(token.balances.get(&owner).copied().unwrap_or(0))
+        })
+    }
+
+    pub(crate) fn transfer(token: AccountId, to: AccountId, value: Balance) -> CallResult<()> {
+        with_token(token, |token| move_tokens(token, contract(), to, value))
+    }
+
+    pub(crate) fn transfer_from(
+        token: AccountId,
+        from: AccountId,
+        to: AccountId,
+        value: Balance,
+    ) -> CallResult<()> {
+        with_token(token, |token| {
+            let allowance = token
+                .allowances
+                .get(&(from, contract()))
+                .copied()
+                .unwrap_or(0);
+            if allowance < value {
+                return Err(TokenError::Rejected(PSP22Error::InsufficientAllowance));
+            }
+
+            move_tokens(token, from, to, value)?;
+            token
+                .allowances
+                .insert((from, contract()), allowance - value);
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,182 @@
+//! Cross-contract calls to the PSP34 collection of the NFT pools
+//!
+//! Off-chain tests can't make cross-contract calls,
+//! so in tests the calls go to the mock PSP34 collection instead.
+
+use crate::utils::TokenError;
+use ink_env::{AccountId, DefaultEnvironment};
+use ink_prelude::string::String;
+use ink_prelude::vec::Vec;
+#[cfg(feature = "std")]
+use ink_storage::traits::StorageLayout;
+use ink_storage::traits::{PackedLayout, SpreadLayout};
+
+/// Token id in the PSP34 collection, as it is defined in the PSP34 standard
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    scale::Encode,
+    scale::Decode,
+    SpreadLayout,
+    PackedLayout,
+)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, StorageLayout))]
+pub enum Id {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    Bytes(Vec<u8>),
+}
+
+/// Errors returned by the PSP34 collection, as they are defined in the PSP34 standard
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum PSP34Error {
+    Custom(String),
+    SelfApprove,
+    NotApproved,
+    TokenExists,
+    TokenNotExists,
+    SafeTransferCheckFailed(String),
+}
+
+/// Result of the call to the PSP34 collection
+pub(crate) type CallResult<T> = Result<T, TokenError<PSP34Error>>;
+
+#[cfg(not(test))]
+pub(crate) use calls::*;
+#[cfg(test)]
+pub(crate) use mock::{owner_of, transfer};
+
+#[cfg(not(test))]
+mod calls {
+    use super::*;
+    use ink_env::call::{build_call, Call, ExecutionInput, Selector};
+
+    /// Selector of `PSP34::owner_of`
+    const OWNER_OF_SELECTOR: [u8; 4] = [0x11, 0x68, 0x62, 0x4d];
+
+    /// Selector of `PSP34::transfer`
+    const TRANSFER_SELECTOR: [u8; 4] = [0x31, 0x28, 0xd6, 0x1b];
+
+    /// Returns the owner of the token `id`, `None` if the token doesn't exist
+    pub(crate) fn owner_of(collection: AccountId, id: Id) -> CallResult<Option<AccountId>> {
+        build_call::<DefaultEnvironment>()
+            .call_type(Call::new().callee(collection))
+            .exec_input(ExecutionInput::new(Selector::new(OWNER_OF_SELECTOR)).push_arg(id))
+            .returns::<Option<AccountId>>()
+            .fire()
+            .map_err(|_| TokenError::CallFailed)
+    }
+
+    /// Transfers the token `id` to `to`
+    ///
+    /// The contract should own the token or be approved to transfer it
+    pub(crate) fn transfer(collection: AccountId, to: AccountId, id: Id) -> CallResult<()> {
+        build_call::<DefaultEnvironment>()
+            .call_type(Call::new().callee(collection))
+            .exec_input(
+                ExecutionInput::new(Selector::new(TRANSFER_SELECTOR))
+                    .push_arg(to)
+                    .push_arg(id)
+                    .push_arg(Vec::<u8>::new()),
+            )
+            .returns::<Result<(), PSP34Error>>()
+            .fire()
+            .map_err(|_| TokenError::CallFailed)?
+            .map_err(TokenError::Rejected)
+    }
+}
+
+/// Mock PSP34 collection for the off-chain tests
+///
+/// Only collections created with `deploy` can be called, the caller of
+/// `transfer` is the contract under test
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[derive(Default)]
+    struct Collection {
+        owners: BTreeMap<Id, AccountId>,
+        approvals: BTreeSet<(AccountId, AccountId, Id)>,
+    }
+
+    thread_local! {
+        static COLLECTIONS: RefCell<BTreeMap<AccountId, Collection>> =
+            const { RefCell::new(BTreeMap::new()) };
+    }
+
+    fn with_collection<R>(
+        collection: AccountId,
+        f: impl FnOnce(&mut Collection) -> CallResult<R>,
+    ) -> CallResult<R> {
+        COLLECTIONS.with(|collections| {
+            collections
+                .borrow_mut()
+                .get_mut(&collection)
+                .ok_or(TokenError::CallFailed)
+                .and_then(f)
+        })
+    }
+
+    /// Creates a collection without any tokens
+    pub(crate) fn deploy(collection: AccountId) {
+        COLLECTIONS.with(|collections| {
+            collections
+                .borrow_mut()
+                .insert(collection, Collection::default())
+        });
+    }
+
+    /// Mints the token `id` to `owner`
+    pub(crate) fn mint(collection: AccountId, owner: AccountId, id: Id) {
+        with_collection(collection, |collection| {
+            collection.owners.insert(id, owner);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    /// Approves `operator` to transfer the token `id` of `owner`
+    pub(crate) fn approve(collection: AccountId, owner: AccountId, operator: AccountId, id: Id) {
+        with_collection(collection, |collection| {
+            collection.approvals.insert((owner, operator, id));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    pub(crate) fn owner_of(collection: AccountId, id: Id) -> CallResult<Option<AccountId>> {
+        with_collection(collection, |collection| {
+            Ok(collection.owners.get(&id).copied())
+        })
+    }
+
+    pub(crate) fn transfer(collection: AccountId, to: AccountId, id: Id) -> CallResult<()> {
+        let caller = ink_env::account_id::<DefaultEnvironment>();
+
+        with_collection(collection, |collection| {
+            let owner = *collection
+                .owners
+                .get(&id)
+                .ok_or(TokenError::Rejected(PSP34Error::TokenNotExists))?;
+
+            if owner != caller && !collection.approvals.remove(&(owner, caller, id.clone())) {
+                return Err(TokenError::Rejected(PSP34Error::NotApproved));
+            }
+
+            collection.owners.insert(id, to);
+
+            Ok(())
+        })
+    }
+}
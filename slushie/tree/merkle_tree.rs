@@ -1,9 +1,8 @@
 #[cfg(feature = "std")]
-use ink_primitives::KeyPtr;
-#[cfg(feature = "std")]
-use ink_storage::traits::{ExtKeyPtr, StorageLayout};
+use ink_storage::traits::StorageLayout;
 use ink_storage::traits::{PackedLayout, SpreadAllocate, SpreadLayout};
 
+use alloc::vec::Vec;
 use plonk_prover::hasher::MerkleTreeHasher;
 /// Merkle tree maximum depth
 use shared::constants::MAX_DEPTH;
@@ -11,53 +10,72 @@ use shared::constants::MAX_DEPTH;
 /// Merkle tree history size
 pub const DEFAULT_ROOT_HISTORY_SIZE: usize = 100;
 
+/// Merkle tree maximum history size
+///
+/// The history is read and written on every deposit and should fit into the storage value
+pub const MAX_ROOT_HISTORY_SIZE: usize = 256;
+
 ///Merkle tree with history for storing commitments in it
+///
+///Depth of the tree is the number of filled subtrees
+///and the history size is the number of roots, both are set on creation
 #[derive(scale::Encode, scale::Decode, PackedLayout, SpreadLayout, SpreadAllocate, PartialEq)]
-#[cfg_attr(feature = "std", derive(Debug, StorageLayout))]
-pub(crate) struct MerkleTree<
-    const DEPTH: usize,
-    const ROOT_HISTORY_SIZE: usize,
-    Hash: MerkleTreeHasher,
-> {
+#[cfg_attr(feature = "std", derive(Debug, StorageLayout, scale_info::TypeInfo))]
+#[cfg_attr(feature = "std", scale_info(skip_type_params(Hash)))]
+pub(crate) struct MerkleTree<Hash: MerkleTreeHasher> {
     ///Current root index in the history
     pub current_root_index: u64,
     /// Next leaf index
     pub next_index: u64,
     ///Hashes last filled subtrees on every level
-    pub filled_subtrees: Array<Hash::Output, DEPTH>,
+    pub filled_subtrees: Vec<Hash::Output>,
     /// Merkle tree roots history
-    pub roots: Array<Hash::Output, ROOT_HISTORY_SIZE>,
+    pub roots: Vec<Hash::Output>,
 }
 
-impl<const DEPTH: usize, const ROOT_HISTORY_SIZE: usize, Hash: MerkleTreeHasher>
-    MerkleTree<DEPTH, ROOT_HISTORY_SIZE, Hash>
-{
-    ///Create merkle tree
-    pub fn new() -> Result<Self, MerkleTreeError> {
-        if DEPTH > MAX_DEPTH {
+impl<Hash: MerkleTreeHasher> MerkleTree<Hash> {
+    ///Create merkle tree with provided depth and root history size
+    pub fn new(depth: usize, root_history_size: usize) -> Result<Self, MerkleTreeError> {
+        if depth > MAX_DEPTH {
             return Err(MerkleTreeError::DepthTooLong);
         }
 
-        if DEPTH == 0 {
+        if depth == 0 {
             return Err(MerkleTreeError::DepthIsZero);
         }
 
-        let roots = Array([Hash::ZEROS[DEPTH - 1]; ROOT_HISTORY_SIZE]);
+        if root_history_size == 0 {
+            return Err(MerkleTreeError::RootHistoryIsEmpty);
+        }
+
+        if root_history_size > MAX_ROOT_HISTORY_SIZE {
+            return Err(MerkleTreeError::RootHistoryTooLong);
+        }
 
-        let mut filled_subtrees: Array<Hash::Output, DEPTH> = Default::default();
-        filled_subtrees.0.copy_from_slice(&Hash::ZEROS[0..DEPTH]);
+        let mut roots = Vec::new();
+        roots.resize(root_history_size, Hash::ZEROS[depth - 1]);
 
         Ok(Self {
             current_root_index: 0,
             next_index: 0,
-            filled_subtrees,
+            filled_subtrees: Hash::ZEROS[0..depth].to_vec(),
             roots,
         })
     }
 
+    /// Get depth of the tree
+    pub fn depth(&self) -> usize {
+        self.filled_subtrees.len()
+    }
+
+    /// Get number of roots kept in the history
+    pub fn root_history_size(&self) -> usize {
+        self.roots.len()
+    }
+
     /// Get last root hash
     pub fn get_last_root(&self) -> Hash::Output {
-        self.roots.0[self.current_root_index as usize]
+        self.roots[self.current_root_index as usize]
     }
 
     /// Check existing provided root in roots history
@@ -66,13 +84,13 @@ impl<const DEPTH: usize, const ROOT_HISTORY_SIZE: usize, Hash: MerkleTreeHasher>
             return false;
         }
 
-        let root_history_size_u64 = ROOT_HISTORY_SIZE as u64;
+        let root_history_size_u64 = self.root_history_size() as u64;
 
         for i in 0..root_history_size_u64 {
             let current_index = ((root_history_size_u64 + self.current_root_index - i)
                 % root_history_size_u64) as usize;
 
-            if root == self.roots.0[current_index] {
+            if root == self.roots[current_index] {
                 return true;
             }
         }
@@ -80,19 +98,42 @@ impl<const DEPTH: usize, const ROOT_HISTORY_SIZE: usize, Hash: MerkleTreeHasher>
         false
     }
 
+    /// Get roots history in the order they appeared, from the oldest to the last root
+    pub fn get_roots(&self) -> Vec<Hash::Output> {
+        let root_history_size_u64 = self.root_history_size() as u64;
+
+        // Initial root and one root for every insertion, but not more than history size
+        let roots_count = core::cmp::min(self.next_index + 1, root_history_size_u64);
+
+        (0..roots_count)
+            .rev()
+            .map(|i| {
+                let index = ((root_history_size_u64 + self.current_root_index - i)
+                    % root_history_size_u64) as usize;
+
+                self.roots[index]
+            })
+            .collect()
+    }
+
+    /// Check that all leaves of the tree are filled
+    pub fn is_full(&self) -> bool {
+        self.next_index == 1 << self.depth()
+    }
+
     ///Insert leaf in the merkle tree
     pub fn insert(&mut self, leaf: Hash::Output) -> Result<usize, MerkleTreeError> {
         let next_index = self.next_index as usize;
 
-        if self.next_index == 1 << DEPTH {
+        if self.is_full() {
             return Err(MerkleTreeError::MerkleTreeIsFull);
         }
 
-        let root_history_size_u64 = ROOT_HISTORY_SIZE as u64;
+        let root_history_size_u64 = self.root_history_size() as u64;
         let mut current_index = next_index;
         let mut current_hash = leaf;
 
-        for i in 0..DEPTH {
+        for i in 0..self.depth() {
             let left;
             let right;
 
@@ -100,9 +141,9 @@ impl<const DEPTH: usize, const ROOT_HISTORY_SIZE: usize, Hash: MerkleTreeHasher>
                 right = Hash::ZEROS[i];
                 left = current_hash;
 
-                self.filled_subtrees.0[i] = current_hash;
+                self.filled_subtrees[i] = current_hash;
             } else {
-                left = self.filled_subtrees.0[i];
+                left = self.filled_subtrees[i];
                 right = current_hash;
             }
 
@@ -112,7 +153,7 @@ impl<const DEPTH: usize, const ROOT_HISTORY_SIZE: usize, Hash: MerkleTreeHasher>
 
         self.current_root_index = (self.current_root_index + 1) % root_history_size_u64;
 
-        self.roots.0[self.current_root_index as usize] = current_hash;
+        self.roots[self.current_root_index as usize] = current_hash;
 
         self.next_index += 1;
 
@@ -129,38 +170,10 @@ pub(crate) enum MerkleTreeError {
     DepthTooLong,
     ///Depth can not be 0
     DepthIsZero,
-}
-
-#[derive(
-    scale::Encode, scale::Decode, PackedLayout, SpreadLayout, SpreadAllocate, PartialEq, Eq,
-)]
-#[cfg_attr(feature = "std", derive(Debug))]
-pub struct Array<T: Default + Clone + Copy, const N: usize>([T; N]);
-
-#[cfg(feature = "std")]
-use ink_metadata::layout::{ArrayLayout, Layout, LayoutKey};
-
-#[cfg(feature = "std")]
-impl<T: Default + Clone + Copy, const N: usize> StorageLayout for Array<T, N>
-where
-    T: StorageLayout + SpreadLayout,
-{
-    fn layout(key_ptr: &mut KeyPtr) -> Layout {
-        let len: u32 = N as u32;
-        let elem_footprint = <T as SpreadLayout>::FOOTPRINT;
-        Layout::Array(ArrayLayout::new(
-            LayoutKey::from(key_ptr.next_for::<[T; N]>()),
-            len,
-            elem_footprint,
-            <T as StorageLayout>::layout(&mut key_ptr.clone()),
-        ))
-    }
-}
-
-impl<T: Default + Clone + Copy, const N: usize> Default for Array<T, N> {
-    fn default() -> Self {
-        Self([Default::default(); N])
-    }
+    ///Root history should keep at least one root
+    RootHistoryIsEmpty,
+    ///Root history size should be in range 1..MAX_ROOT_HISTORY_SIZE
+    RootHistoryTooLong,
 }
 
 #[cfg(test)]
@@ -168,6 +181,7 @@ mod tests {
     use dusk_bls12_381::BlsScalar;
     use ink_env::hash::{Blake2x256, CryptoHash};
     use plonk_prover::hasher::{Blake, Poseidon};
+    use plonk_prover::merkle_tree::MerkleTree as SparseMerkleTree;
     use shared::functions::bytes_to_u64;
 
     use super::*;
@@ -175,17 +189,17 @@ mod tests {
 
     #[test]
     fn test_get_zero_root() {
-        let tree = MerkleTree::<TEST_MAX_DEPTH, 30, Blake>::new().unwrap();
+        let tree = MerkleTree::<Blake>::new(TEST_MAX_DEPTH, 30).unwrap();
         assert_eq!(tree.get_last_root(), Blake::ZEROS[TEST_MAX_DEPTH - 1]);
 
         for i in 0..TEST_MAX_DEPTH {
-            assert_eq!(tree.filled_subtrees.0[i], Blake::ZEROS[i]);
+            assert_eq!(tree.filled_subtrees[i], Blake::ZEROS[i]);
         }
     }
 
     #[test]
     fn test_insert() {
-        let mut tree = MerkleTree::<TEST_MAX_DEPTH, 30, Blake>::new().unwrap();
+        let mut tree = MerkleTree::<Blake>::new(TEST_MAX_DEPTH, 30).unwrap();
         assert_eq!(tree.get_last_root(), Blake::ZEROS[TEST_MAX_DEPTH - 1]);
 
         tree.insert([4; 32]).unwrap();
@@ -198,7 +212,7 @@ mod tests {
 
     #[test]
     fn test_tree_indexes() {
-        let mut tree = MerkleTree::<TEST_MAX_DEPTH, 30, Blake>::new().unwrap();
+        let mut tree = MerkleTree::<Blake>::new(TEST_MAX_DEPTH, 30).unwrap();
 
         for i in 0..2usize.pow(TEST_MAX_DEPTH as u32) {
             let index = tree.insert([i as u8; 32]).unwrap();
@@ -209,12 +223,15 @@ mod tests {
 
     #[test]
     fn test_error_when_tree_is_full() {
-        let mut tree = MerkleTree::<3, 30, Blake>::new().unwrap();
+        let mut tree = MerkleTree::<Blake>::new(3, 30).unwrap();
 
         for i in 0..2usize.pow(3) {
+            assert!(!tree.is_full());
             tree.insert([i as u8 + 1; 32]).unwrap();
         }
 
+        assert!(tree.is_full());
+
         let err = tree.insert([6; 32]);
 
         assert_eq!(err, Err(MerkleTreeError::MerkleTreeIsFull));
@@ -222,23 +239,57 @@ mod tests {
 
     #[test]
     fn test_error_when_tree_depth_too_long() {
-        const MAX_DEPTH_PLUS_1: usize = MAX_DEPTH + 1;
-
-        let tree = MerkleTree::<MAX_DEPTH_PLUS_1, 30, Blake>::new();
+        let tree = MerkleTree::<Blake>::new(MAX_DEPTH + 1, 30);
 
         assert_eq!(tree, Err(MerkleTreeError::DepthTooLong));
     }
 
     #[test]
     fn test_error_when_tree_depth_is_0() {
-        let tree = MerkleTree::<0, 30, Blake>::new();
+        let tree = MerkleTree::<Blake>::new(0, 30);
 
         assert_eq!(tree, Err(MerkleTreeError::DepthIsZero));
     }
 
+    #[test]
+    fn test_error_when_root_history_is_empty() {
+        let tree = MerkleTree::<Blake>::new(TEST_MAX_DEPTH, 0);
+
+        assert_eq!(tree, Err(MerkleTreeError::RootHistoryIsEmpty));
+    }
+
+    #[test]
+    fn test_error_when_root_history_too_long() {
+        let tree = MerkleTree::<Blake>::new(TEST_MAX_DEPTH, MAX_ROOT_HISTORY_SIZE + 1);
+
+        assert_eq!(tree, Err(MerkleTreeError::RootHistoryTooLong));
+    }
+
+    #[test]
+    fn test_tree_with_max_root_history_size() {
+        let tree = MerkleTree::<Blake>::new(TEST_MAX_DEPTH, MAX_ROOT_HISTORY_SIZE).unwrap();
+
+        assert_eq!(tree.root_history_size(), MAX_ROOT_HISTORY_SIZE);
+    }
+
+    #[test]
+    fn test_tree_with_provided_depth_and_root_history_size() {
+        let mut tree = MerkleTree::<Blake>::new(2, 3).unwrap();
+        assert_eq!(tree.depth(), 2);
+        assert_eq!(tree.root_history_size(), 3);
+        assert_eq!(tree.get_last_root(), Blake::ZEROS[1]);
+
+        for i in 0..4 {
+            tree.insert([i as u8 + 1; 32]).unwrap();
+        }
+
+        assert!(tree.is_full());
+        assert_eq!(tree.get_roots().len(), 3);
+    }
+
     #[test]
     fn test_is_known_root() {
-        let mut tree = MerkleTree::<TEST_MAX_DEPTH, 30, Blake>::new().unwrap();
+        let mut tree = MerkleTree::<Blake>::new(TEST_MAX_DEPTH, 30).unwrap();
 
         let mut known_roots = vec![Blake::ZEROS[TEST_MAX_DEPTH - 1]];
 
@@ -256,7 +307,7 @@ mod tests {
 
     #[test]
     fn test_roots_field() {
-        let mut tree = MerkleTree::<TEST_MAX_DEPTH, 30, Blake>::new().unwrap();
+        let mut tree = MerkleTree::<Blake>::new(TEST_MAX_DEPTH, 30).unwrap();
 
         let mut roots = vec![Blake::ZEROS[TEST_MAX_DEPTH - 1]; 30];
 
@@ -268,18 +319,39 @@ mod tests {
             roots[index as usize] = root;
         }
 
-        assert_eq!(&tree.roots.0[..], &roots[..]);
+        assert_eq!(&tree.roots[..], &roots[..]);
+    }
+
+    #[test]
+    fn test_get_roots() {
+        let mut tree = MerkleTree::<Blake>::new(TEST_MAX_DEPTH, 5).unwrap();
+
+        let mut roots = vec![tree.get_last_root()];
+        assert_eq!(tree.get_roots(), roots);
+
+        for i in 0..12 {
+            tree.insert([i as u8 + 1; 32]).unwrap();
+            roots.push(tree.get_last_root());
+
+            // Only the last roots are kept in history
+            let kept_roots = &roots[roots.len().saturating_sub(5)..];
+            assert_eq!(tree.get_roots(), kept_roots);
+
+            for root in kept_roots {
+                assert!(tree.is_known_root(*root));
+            }
+        }
     }
 
     #[test]
     fn test_check_tree_zeros_correctness() {
-        let mut tree = MerkleTree::<TEST_MAX_DEPTH, 3, Blake>::new().unwrap();
+        let mut tree = MerkleTree::<Blake>::new(TEST_MAX_DEPTH, 3).unwrap();
         for _i in 0..2u64.pow(TEST_MAX_DEPTH as u32) {
             tree.insert(Blake::ZEROS[0]).unwrap();
         }
 
         for i in 0..TEST_MAX_DEPTH {
-            assert_eq!(tree.filled_subtrees.0[i], Blake::ZEROS[i]);
+            assert_eq!(tree.filled_subtrees[i], Blake::ZEROS[i]);
         }
     }
 
@@ -297,17 +369,17 @@ mod tests {
 
     #[test]
     fn test_get_zero_root_poseidon() {
-        let tree = MerkleTree::<TEST_MAX_DEPTH, 30, Poseidon>::new().unwrap();
+        let tree = MerkleTree::<Poseidon>::new(TEST_MAX_DEPTH, 30).unwrap();
         assert_eq!(tree.get_last_root(), Poseidon::ZEROS[TEST_MAX_DEPTH - 1]);
 
         for i in 0..TEST_MAX_DEPTH {
-            assert_eq!(tree.filled_subtrees.0[i], Poseidon::ZEROS[i]);
+            assert_eq!(tree.filled_subtrees[i], Poseidon::ZEROS[i]);
         }
     }
 
     #[test]
     fn test_insert_poseidon() {
-        let mut tree = MerkleTree::<TEST_MAX_DEPTH, 30, Poseidon>::new().unwrap();
+        let mut tree = MerkleTree::<Poseidon>::new(TEST_MAX_DEPTH, 30).unwrap();
         assert_eq!(tree.get_last_root(), Poseidon::ZEROS[TEST_MAX_DEPTH - 1]);
 
         tree.insert([4; 32]).unwrap();
@@ -320,7 +392,7 @@ mod tests {
 
     #[test]
     fn test_tree_indexes_poseidon() {
-        let mut tree = MerkleTree::<TEST_MAX_DEPTH, 30, Poseidon>::new().unwrap();
+        let mut tree = MerkleTree::<Poseidon>::new(TEST_MAX_DEPTH, 30).unwrap();
 
         for i in 0..2usize.pow(TEST_MAX_DEPTH as u32) {
             let index = tree.insert([i as u8; 32]).unwrap();
@@ -331,7 +403,7 @@ mod tests {
 
     #[test]
     fn test_error_when_tree_is_full_poseidon() {
-        let mut tree = MerkleTree::<TEST_MAX_DEPTH, 30, Poseidon>::new().unwrap();
+        let mut tree = MerkleTree::<Poseidon>::new(TEST_MAX_DEPTH, 30).unwrap();
 
         for i in 0..2usize.pow(TEST_MAX_DEPTH as u32) {
             tree.insert([i as u8; 32]).unwrap();
@@ -344,23 +416,21 @@ mod tests {
 
     #[test]
     fn test_error_when_tree_depth_too_long_poseidon() {
-        const MAX_DEPTH_PLUS_1: usize = MAX_DEPTH + 1;
-
-        let tree = MerkleTree::<MAX_DEPTH_PLUS_1, 30, Poseidon>::new();
+        let tree = MerkleTree::<Poseidon>::new(MAX_DEPTH + 1, 30);
 
         assert_eq!(tree, Err(MerkleTreeError::DepthTooLong));
     }
 
     #[test]
     fn test_error_when_tree_depth_is_0_poseidon() {
-        let tree = MerkleTree::<0, 30, Poseidon>::new();
+        let tree = MerkleTree::<Poseidon>::new(0, 30);
 
         assert_eq!(tree, Err(MerkleTreeError::DepthIsZero));
     }
 
     #[test]
     fn test_is_known_root_poseidon() {
-        let mut tree = MerkleTree::<TEST_MAX_DEPTH, 30, Poseidon>::new().unwrap();
+        let mut tree = MerkleTree::<Poseidon>::new(TEST_MAX_DEPTH, 30).unwrap();
 
         let mut known_roots = vec![Poseidon::ZEROS[TEST_MAX_DEPTH - 1]];
 
@@ -378,7 +448,7 @@ mod tests {
 
     #[test]
     fn test_roots_field_poseidon() {
-        let mut tree = MerkleTree::<TEST_MAX_DEPTH, 30, Poseidon>::new().unwrap();
+        let mut tree = MerkleTree::<Poseidon>::new(TEST_MAX_DEPTH, 30).unwrap();
 
         let mut roots = vec![Poseidon::ZEROS[TEST_MAX_DEPTH - 1]; 30];
 
@@ -390,18 +460,18 @@ mod tests {
             roots[index as usize] = root;
         }
 
-        assert_eq!(&tree.roots.0[..], &roots[..]);
+        assert_eq!(&tree.roots[..], &roots[..]);
     }
 
     #[test]
     fn test_check_tree_zeros_correctness_poseidon() {
-        let mut tree = MerkleTree::<TEST_MAX_DEPTH, 30, Poseidon>::new().unwrap();
+        let mut tree = MerkleTree::<Poseidon>::new(TEST_MAX_DEPTH, 30).unwrap();
         for _i in 0..2u64.pow(TEST_MAX_DEPTH as u32) {
             tree.insert(Poseidon::ZEROS[0]).unwrap();
         }
 
         for i in 0..TEST_MAX_DEPTH {
-            assert_eq!(tree.filled_subtrees.0[i], Poseidon::ZEROS[i]);
+            assert_eq!(tree.filled_subtrees[i], Poseidon::ZEROS[i]);
         }
     }
 
@@ -418,4 +488,76 @@ mod tests {
             result = dusk_poseidon::sponge::hash(&[result, result]);
         }
     }
+
+    #[test]
+    fn test_sparse_tree_matches_contract_tree() {
+        for _ in 0..20 {
+            let mut tree = MerkleTree::<Poseidon>::new(TEST_MAX_DEPTH, 30).unwrap();
+            let mut sparse_tree = SparseMerkleTree::<TEST_MAX_DEPTH, Poseidon>::new().unwrap();
+
+            assert_eq!(tree.get_last_root(), sparse_tree.root());
+
+            let inserts_count = rand::random::<usize>() % 100 + 1;
+
+            for _ in 0..inserts_count {
+                let leaf = rand::random::<[u8; 32]>();
+
+                assert_eq!(
+                    tree.insert(leaf).unwrap(),
+                    sparse_tree.append(leaf).unwrap()
+                );
+                assert_eq!(tree.get_last_root(), sparse_tree.root());
+                assert!(sparse_tree.is_known_root(tree.get_last_root()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_sparse_tree_openings_lead_to_contract_root() {
+        const DEPTH: usize = 4;
+
+        for leaves_count in 1..=2usize.pow(DEPTH as u32) {
+            let mut tree = MerkleTree::<Poseidon>::new(DEPTH, 30).unwrap();
+            let mut sparse_tree = SparseMerkleTree::<DEPTH, Poseidon>::new().unwrap();
+
+            let leaves: Vec<[u8; 32]> = (0..leaves_count).map(|_| rand::random()).collect();
+            for leaf in &leaves {
+                tree.insert(*leaf).unwrap();
+                sparse_tree.append(*leaf).unwrap();
+            }
+
+            // Every opening, including the ones with partly filled sister subtrees,
+            // gives the contract root
+            for (leaf_index, leaf) in leaves.iter().enumerate() {
+                let opening = sparse_tree.opening(leaf_index).unwrap();
+                let path = sparse_tree.path(leaf_index).unwrap();
+
+                let mut current_hash = *leaf;
+                for (sister_hash, direction) in opening.iter().zip(path.iter()) {
+                    current_hash = if *direction == 0 {
+                        Poseidon::hash_left_right(current_hash, *sister_hash)
+                    } else {
+                        Poseidon::hash_left_right(*sister_hash, current_hash)
+                    };
+                }
+
+                assert_eq!(current_hash, tree.get_last_root());
+            }
+        }
+    }
+
+    #[test]
+    fn test_sparse_tree_is_full_with_contract_tree() {
+        let mut tree = MerkleTree::<Poseidon>::new(3, 30).unwrap();
+        let mut sparse_tree = SparseMerkleTree::<3, Poseidon>::new().unwrap();
+
+        for i in 0..2usize.pow(3) {
+            tree.insert([i as u8 + 1; 32]).unwrap();
+            sparse_tree.append([i as u8 + 1; 32]).unwrap();
+        }
+
+        assert_eq!(tree.insert([9; 32]), Err(MerkleTreeError::MerkleTreeIsFull));
+        assert!(sparse_tree.append([9; 32]).is_err());
+        assert_eq!(tree.get_last_root(), sparse_tree.root());
+    }
 }
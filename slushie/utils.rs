@@ -1,24 +1,89 @@
-use crate::slushie::PublicInputs;
+use crate::slushie::{PublicInputs, VerifyingKey};
+use alloc::{vec, vec::Vec};
 use ink_env::AccountId;
 
-use plonk_prover::verify_with_vd;
+use plonk_prover::{ProofPublicInputs, SlushieVerifier};
+
+/// Create verifier for the verifying key, returns `None` if the key can't be deserialized
+pub(crate) fn create_verifier(key: &VerifyingKey) -> Option<SlushieVerifier> {
+    let opening_key = key.opening_key.as_slice().try_into().ok()?;
+
+    SlushieVerifier::new(&key.verifier_data, opening_key).ok()
+}
 
 pub(crate) fn check_proof(
-    vd: &[u8],
-    opening_key: &[u8; 240],
+    verifier: &SlushieVerifier,
     public_inputs: &PublicInputs,
     relayer: AccountId,
+    denomination: u128,
+    domain: [u8; 32],
 ) -> bool {
-    verify_with_vd(
-        vd,
-        opening_key,
-        public_inputs.nullifier_hash,
-        public_inputs.root,
-        *public_inputs.recipient.as_ref(),
-        *relayer.as_ref(),
-        public_inputs.fee,
-        &public_inputs.proof,
-    )
-    .map(|_| true)
-    .unwrap_or(false)
+    verifier
+        .verify(
+            &proof_public_inputs(public_inputs, relayer, denomination, domain),
+            &public_inputs.proof,
+        )
+        .is_ok()
+}
+
+/// Check the proofs of the batch, a proof is accepted if any of the verifiers accepts it
+///
+/// Returns the result for every proof in the same order
+pub(crate) fn check_proofs(
+    verifiers: &[SlushieVerifier],
+    batch: &[PublicInputs],
+    relayer: AccountId,
+    denomination: u128,
+    domain: [u8; 32],
+) -> Vec<bool> {
+    let mut accepted = vec![false; batch.len()];
+
+    for verifier in verifiers {
+        // Proofs accepted by the previous verifiers are not checked again
+        let (indexes, proofs): (Vec<usize>, Vec<_>) = batch
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !accepted[*i])
+            .map(|(i, public_inputs)| {
+                let inputs = proof_public_inputs(public_inputs, relayer, denomination, domain);
+                (i, (inputs, public_inputs.proof))
+            })
+            .unzip();
+
+        if indexes.is_empty() {
+            break;
+        }
+
+        for (i, result) in indexes.into_iter().zip(verifier.verify_batch(&proofs)) {
+            accepted[i] = result.is_ok();
+        }
+    }
+
+    accepted
+}
+
+fn proof_public_inputs(
+    public_inputs: &PublicInputs,
+    relayer: AccountId,
+    denomination: u128,
+    domain: [u8; 32],
+) -> ProofPublicInputs {
+    ProofPublicInputs {
+        nullifier_hash: public_inputs.nullifier_hash,
+        root: public_inputs.root,
+        recipient: *public_inputs.recipient.as_ref(),
+        relayer: *relayer.as_ref(),
+        fee: public_inputs.fee,
+        denomination,
+        domain,
+    }
+}
+
+/// Errors which may be returned from the call to the token contract
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum TokenError<E> {
+    /// Call to the token contract failed, e.g. the contract does not exist or trapped
+    CallFailed,
+    /// Token contract rejected the call with its own error
+    Rejected(E),
 }
@@ -20,7 +20,8 @@ export async function deployContract(
   const storageDepositLimit = null;
   const tx = code.tx.new(
     { gasLimit, storageDepositLimit },
-    BigInt(depositSize) * 1000000000000n
+    [BigInt(depositSize) * 1000000000000n],
+    api.genesisHash
   );
 
   // Send transaction and create contract promise when it will be finished
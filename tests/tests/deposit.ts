@@ -8,7 +8,7 @@ export async function deposit(
   contract: ContractPromise,
   signer: KeyringPair,
   depositSize: number
-): Promise<[number, number, Uint8Array, Uint8Array]> {
+): Promise<[Uint8Array, Uint8Array, Uint8Array, Uint8Array]> {
   // Generate commitment
   const [k, r, c, h] = generate_commitment();
 
@@ -22,6 +22,7 @@ export async function deposit(
         gasLimit: gasLimit,
         value: BigInt(depositSize) * 1000000000000n,
       },
+      BigInt(depositSize) * 1000000000000n,
       c
     )
     .signAndSend(signer, (result) => {
@@ -30,11 +31,15 @@ export async function deposit(
           if (method == "ContractEmitted") {
             // Event format is:
             // 1 byte for event type
+            // 16 bytes for denomination
+            // 4 bytes for epoch
             // 32 bytes for commitment
+            // 8 bytes for leaf index
+            // 32 bytes for root
             // 8 bytes for timestamp
             const depositEventData = eventDataToBytes(data);
-            // To get commitment, we should use bytes from 1 to 33
-            const commitment = depositEventData.slice(1, 33);
+            // To get commitment, we should use bytes from 21 to 53
+            const commitment = depositEventData.slice(21, 53);
 
             expect(c).toEqual(commitment);
 
@@ -47,5 +52,5 @@ export async function deposit(
   // Wait until deposit will be finished
   await wait(3000);
 
-  return [k as number, r as number, c as Uint8Array, h as Uint8Array];
+  return [k as Uint8Array, r as Uint8Array, c as Uint8Array, h as Uint8Array];
 }
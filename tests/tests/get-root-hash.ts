@@ -1,18 +1,25 @@
 import { ContractPromise } from "@polkadot/api-contract";
+import { Result } from "@polkadot/types";
+import { Codec } from "@polkadot/types/types";
 import { hexStringToBytes } from "./utils";
 
-/** Read last root hash from contract */
+/** Read last root hash of the pool from contract */
 export async function getRootHash(
   contract: ContractPromise,
-  signerAddress: string
+  signerAddress: string,
+  depositSize: number
 ): Promise<Uint8Array> {
   const gasLimit = -1;
   const storageDepositLimit = null;
 
-  const { result, output } = await contract.query.getRootHash(signerAddress, {
-    gasLimit,
-    storageDepositLimit,
-  });
+  const { result, output } = await contract.query.getRootHash(
+    signerAddress,
+    {
+      gasLimit,
+      storageDepositLimit,
+    },
+    BigInt(depositSize) * 1000000000000n
+  );
 
   expect(result.isOk).toBe(true);
 
@@ -20,6 +27,8 @@ export async function getRootHash(
     throw Error("Output is null");
   }
  
-  // Get hex string from output and convert it to the bytes array
-  return hexStringToBytes(output.toHex());
+  // Get hex string from Ok output and convert it to the bytes array
+  const rootHash = (output as unknown as Result<Codec, Codec>).asOk;
+
+  return hexStringToBytes(rootHash.toHex());
 }
@@ -23,8 +23,8 @@ let alice: KeyringPair;
 let depositSize: number;
 const commitments: Uint8Array[] = [];
 const nullifiers_hash: Uint8Array[] = [];
-const nullifiers: number[] = [];
-const randomnesses: number[] = [];
+const nullifiers: Uint8Array[] = [];
+const randomnesses: Uint8Array[] = [];
 
 // Setup connection with local node and deploy contract
 beforeAll(async () => {
@@ -80,7 +80,7 @@ describe("Withdraws with wrong inputs", () => {
       depositSize,
       nullifiers_hash[0],
       nullifiers[0],
-      randomnesses[0] + 1,
+      withFlippedBit(randomnesses[0]),
       0,
       keyring.decodeAddress("5D9x7yc3EcKoGPSETYRMWYVPmjpukbqvUHF6sRLvs5yktfeU"),
       commitments,
@@ -97,7 +97,7 @@ describe("Withdraws with wrong inputs", () => {
       alice,
       depositSize,
       nullifiers_hash[0],
-      nullifiers[0] + 1,
+      withFlippedBit(nullifiers[0]),
       randomnesses[0],
       0,
       keyring.decodeAddress("5D9x7yc3EcKoGPSETYRMWYVPmjpukbqvUHF6sRLvs5yktfeU"),
@@ -155,11 +155,19 @@ afterAll(async () => {
 
 /** Make deposit and check root hash changing */
 async function depositTest(): Promise<number> {
-  const initialRootHash = await getRootHash(contract, alice.address);
+  const initialRootHash = await getRootHash(
+    contract,
+    alice.address,
+    depositSize
+  );
 
   const [k, r, c, h] = await deposit(contract, alice, depositSize);
 
-  const changedRootHash = await getRootHash(contract, alice.address);
+  const changedRootHash = await getRootHash(
+    contract,
+    alice.address,
+    depositSize
+  );
 
   expect(changedRootHash).not.toBe(initialRootHash);
 
@@ -191,3 +199,11 @@ async function withdrawTest(
     useProof
   );
 }
+
+/** Copy secret bytes with the lowest bit of the first byte flipped */
+function withFlippedBit(secret: Uint8Array): Uint8Array {
+  const changed = new Uint8Array(secret);
+  changed[0] ^= 1;
+
+  return changed;
+}
@@ -1,7 +1,11 @@
 import { ContractPromise } from "@polkadot/api-contract";
 import Keyring from "@polkadot/keyring";
 import { KeyringPair } from "@polkadot/keyring/types";
-import { generate_tree_opening, generate_proof_with_pd } from "slushie";
+import {
+  generate_tree_opening,
+  generate_proof_with_pd,
+  generate_withdrawal_domain,
+} from "slushie";
 import { getRootHash } from "./get-root-hash";
 import { getBalance, wait } from "./utils";
 
@@ -11,8 +15,8 @@ export async function withdraw(
   signer: KeyringPair,
   depositSize: number,
   nullifierHash: Uint8Array,
-  k: number,
-  r: number,
+  k: Uint8Array,
+  r: Uint8Array,
   leafIndex: number,
   recipient: Uint8Array,
   commitments: Uint8Array[],
@@ -23,7 +27,7 @@ export async function withdraw(
   const storageDepositLimit = null;
 
   // Get root hash from contract
-  const root = await getRootHash(contract, signer.address);
+  const root = await getRootHash(contract, signer.address, depositSize);
 
   // Generate tree opening using flattened array of commitments and leaf index
   const [treeOpening] = generate_tree_opening(
@@ -44,7 +48,13 @@ export async function withdraw(
       r,
       recipient,
       keyring.decodeAddress(signer.address),
-      0n
+      0n,
+      (BigInt(depositSize) * 1000000000000n).toString(),
+      // Proof is bound to the contract and the chain
+      generate_withdrawal_domain(
+        keyring.decodeAddress(contract.address),
+        contract.api.genesisHash.toU8a()
+      )
     );
   } else {
     // Or use it from parameters
@@ -62,6 +72,7 @@ export async function withdraw(
         storageDepositLimit: storageDepositLimit,
         gasLimit: gasLimit,
       },
+      BigInt(depositSize) * 1000000000000n,
       {
         nullifierHash: nullifierHash,
         root: root,